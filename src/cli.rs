@@ -0,0 +1,183 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::{
+    game::explorer::DEFAULT_MAX_ROOMS,
+    vm::{
+        breakpoints::BreakpointCommand, memory::AMOUNT_REGISTERS, savestate::SAVESTATE_DIRECTORY,
+        transcript::TRANSCRIPT_DIRECTORY, HISTORY_FILE_PATH,
+    },
+};
+
+/// Virtual machine, debugger and tools for the Synacor challenge.
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+    /// Directory the savestate slots are stored in.
+    #[arg(long, global = true, default_value = SAVESTATE_DIRECTORY)]
+    pub savestate_dir: PathBuf,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Runs a program headless on stdin and stdout.
+    Run {
+        #[command(flatten)]
+        session: SessionArgs,
+        /// Slot the final state is saved into.
+        #[arg(long, value_name = "SLOT")]
+        save_state: Option<String>,
+    },
+    /// Runs a program in the terminal viewer.
+    Tui {
+        #[command(flatten)]
+        session: SessionArgs,
+    },
+    /// Prints the disassembly of a program.
+    Disasm { binary: PathBuf },
+    /// Prints the basic blocks and call graph of a program.
+    Analyze {
+        binary: PathBuf,
+        /// Print the call graph in Graphviz DOT format instead.
+        #[arg(long)]
+        dot: bool,
+    },
+    /// Assembles a source file into a program.
+    Asm { source: PathBuf, output: PathBuf },
+    /// Checks a replay against a program without further input.
+    Replay {
+        binary: PathBuf,
+        replay: PathBuf,
+        /// Slot the final state is saved into.
+        #[arg(long, value_name = "SLOT")]
+        save_state: Option<String>,
+        /// Print the program output.
+        #[arg(long)]
+        verbose: bool,
+    },
+    /// Prints a trace file, optionally limited to a range of program counters.
+    Trace {
+        trace: PathBuf,
+        from: Option<u16>,
+        to: Option<u16>,
+    },
+    /// Runs a replay and prints the codes table of the README with the codes found.
+    Codes {
+        binary: PathBuf,
+        replay: PathBuf,
+        /// File with the expected code hashes, one per line or the README table.
+        #[arg(long, value_name = "FILE")]
+        code_hashes: Option<PathBuf>,
+        /// Text searched for codes as well, e.g. `docs/arch-spec.txt`.
+        #[arg(long, value_name = "FILE")]
+        scan: Vec<PathBuf>,
+    },
+    /// Solves parts of the challenge automatically.
+    Solve {
+        #[command(subcommand)]
+        puzzle: Puzzle,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Puzzle {
+    /// Finds the eighth register value which passes the teleporter confirmation.
+    Teleporter {
+        /// Threads to search with, 0 uses all cores.
+        #[arg(long, default_value_t = 0)]
+        threads: usize,
+        /// Program to check the value in, by using the teleporter after the replay.
+        #[arg(long, requires = "replay")]
+        binary: Option<PathBuf>,
+        /// Replay that leads to the point where the teleporter is used the second time.
+        #[arg(long, requires = "binary")]
+        replay: Option<PathBuf>,
+        /// Slot the state after using the teleporter is saved into.
+        #[arg(long, value_name = "SLOT", requires = "binary")]
+        save_state: Option<String>,
+    },
+    /// Finds the shortest orb walk through the vault lock and prints it as replay lines.
+    Vault {
+        binary: PathBuf,
+        /// Savestate slot to start from, e.g. one saved on the beach.
+        #[arg(long, value_name = "SLOT")]
+        load_state: Option<String>,
+        /// Replay that leads into the vault antechamber, run after loading the slot.
+        #[arg(long)]
+        replay: Option<PathBuf>,
+    },
+    /// Explores every exit breadth-first and writes the world map in the `docs/map.json` format.
+    Map {
+        binary: PathBuf,
+        json_output: PathBuf,
+        /// Also write the map in Graphviz DOT format.
+        dot_output: Option<PathBuf>,
+        /// Replay fed in before exploring, e.g. to start at a later part of the game.
+        #[arg(long)]
+        replay: Option<PathBuf>,
+        #[arg(long, default_value_t = DEFAULT_MAX_ROOMS)]
+        max_rooms: usize,
+    },
+}
+
+/// Options shared by the headless and the viewer session.
+#[derive(Debug, Args)]
+pub struct SessionArgs {
+    /// Program binary, e.g. `payloads/challenge.bin`.
+    pub binary: PathBuf,
+    /// Replay fed in at the start, defaults to the history file.
+    #[arg(long)]
+    pub replay: Option<PathBuf>,
+    /// File the typed input is written to, also replayed at the start.
+    #[arg(long, default_value = HISTORY_FILE_PATH)]
+    pub history: PathBuf,
+    /// Start without replaying anything.
+    #[arg(long)]
+    pub no_replay: bool,
+    /// Savestate slot to start from instead of replaying.
+    #[arg(long, value_name = "SLOT")]
+    pub load_state: Option<String>,
+    /// Record an execution trace into this file.
+    #[arg(long)]
+    pub trace: Option<PathBuf>,
+    /// Directory the timestamped session transcript is written to.
+    #[arg(long, default_value = TRANSCRIPT_DIRECTORY)]
+    pub transcript_dir: PathBuf,
+    /// Do not write a session transcript.
+    #[arg(long)]
+    pub no_transcript: bool,
+    /// File with the expected code hashes, one per line or the README table.
+    #[arg(long, value_name = "FILE")]
+    pub code_hashes: Option<PathBuf>,
+    /// Initial register value, e.g. `r7=25734`.
+    #[arg(long = "setr", value_name = "rN=VALUE", value_parser = parse_register_override)]
+    pub registers: Vec<(usize, u16)>,
+    /// Breakpoint added at the start, e.g. `5489` or `5489 hits 2 if r7==0`.
+    #[arg(long = "break", value_name = "PC", value_parser = parse_breakpoint)]
+    pub breakpoints: Vec<BreakpointCommand>,
+}
+
+fn parse_register_override(text: &str) -> Result<(usize, u16), String> {
+    let (register, value) = text
+        .split_once('=')
+        .ok_or_else(|| "expected rN=VALUE".to_string())?;
+    let register = register
+        .trim_start_matches('r')
+        .parse::<usize>()
+        .ok()
+        .filter(|register| *register < AMOUNT_REGISTERS)
+        .ok_or_else(|| format!("invalid register '{}'", register))?;
+    let value = value
+        .parse::<u16>()
+        .map_err(|error| format!("invalid value '{}': {}", value, error))?;
+    Ok((register, value))
+}
+
+fn parse_breakpoint(text: &str) -> Result<BreakpointCommand, String> {
+    let arguments: Vec<&str> = ["add"].into_iter().chain(text.split_whitespace()).collect();
+    BreakpointCommand::parse(&arguments)
+        .ok_or_else(|| "expected PC [hits N] [if rN==VALUE]".to_string())
+}
@@ -0,0 +1,312 @@
+use std::fmt::Display;
+
+use crate::vm::VirtualMachine;
+
+use super::parser::GameState;
+
+const NUMBER_WORDS: [&str; 10] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
+const SHAPES: [(&str, i64); 7] = [
+    ("triangle", 3),
+    ("square", 4),
+    ("pentagon", 5),
+    ("hexagon", 6),
+    ("heptagon", 7),
+    ("octagon", 8),
+    ("nonagon", 9),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoinError {
+    /// The current room does not show an equation.
+    NoEquation,
+    /// The inventory holds fewer coins than there are open slots.
+    NotEnoughCoins(usize, usize),
+    /// The description of the coin does not tell its value.
+    UnknownCoin(String),
+    NoSolution,
+}
+
+impl Display for CoinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoEquation => write!(f, "no equation in this room"),
+            Self::NotEnoughCoins(coins, slots) => {
+                write!(f, "{} coins for {} open slots", coins, slots)
+            }
+            Self::UnknownCoin(coin) => write!(f, "value of the {} is unknown", coin),
+            Self::NoSolution => write!(f, "no order of the coins solves the equation"),
+        }
+    }
+}
+
+impl std::error::Error for CoinError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// An open slot, `_`.
+    Slot,
+    /// A slot already holding a coin.
+    Value(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Term {
+    pub operand: Operand,
+    pub exponent: u32,
+}
+
+/// Equation on the monument, e.g. `_ + _ * _^2 + _^3 - _ = 399`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Equation {
+    pub terms: Vec<Term>,
+    /// Operators between the terms, `+`, `-` or `*`.
+    pub operators: Vec<char>,
+    pub result: i64,
+}
+
+impl Equation {
+    /// Parses the first line of the text that looks like an equation.
+    pub fn find(text: &str) -> Option<Self> {
+        text.lines()
+            .filter(|line| line.contains('='))
+            .find_map(Self::parse)
+    }
+
+    pub fn parse(line: &str) -> Option<Self> {
+        let (expression, result) = line.split_once('=')?;
+        let result = result.trim().parse::<i64>().ok()?;
+        let mut terms = vec![];
+        let mut operators = vec![];
+
+        for (idx, token) in expression.split_whitespace().enumerate() {
+            if idx % 2 == 1 {
+                match token {
+                    "+" | "-" | "*" => operators.push(token.chars().next()?),
+                    _ => return None,
+                }
+                continue;
+            }
+            let (operand, exponent) = match token.split_once('^') {
+                Some((operand, exponent)) => (operand, exponent.parse::<u32>().ok()?),
+                None => (token, 1),
+            };
+            let operand = match operand {
+                "_" => Operand::Slot,
+                value => Operand::Value(value.parse::<i64>().ok()?),
+            };
+            terms.push(Term { operand, exponent });
+        }
+
+        (!terms.is_empty() && terms.len() == operators.len() + 1).then_some(Self {
+            terms,
+            operators,
+            result,
+        })
+    }
+
+    pub fn open_slots(&self) -> usize {
+        self.terms
+            .iter()
+            .filter(|term| term.operand == Operand::Slot)
+            .count()
+    }
+
+    /// Value of the left side with the open slots filled in order, `*` binding tighter.
+    pub fn evaluate(&self, slots: &[i64]) -> i64 {
+        let mut slots = slots.iter();
+        let mut values = self.terms.iter().map(|term| {
+            let value = match term.operand {
+                Operand::Slot => *slots.next().unwrap_or(&0),
+                Operand::Value(value) => value,
+            };
+            value.pow(term.exponent)
+        });
+
+        let mut sum = 0;
+        let mut product = values.next().unwrap_or(0);
+        for (operator, value) in self.operators.iter().zip(values) {
+            match operator {
+                '*' => product *= value,
+                '-' => {
+                    sum += product;
+                    product = -value;
+                }
+                _ => {
+                    sum += product;
+                    product = value;
+                }
+            }
+        }
+        sum + product
+    }
+
+    /// Order in which the values fill the open slots, as indices into `values`.
+    pub fn solve(&self, values: &[i64]) -> Option<Vec<usize>> {
+        let mut order: Vec<usize> = (0..values.len()).collect();
+        let mut found = None;
+        permute(&mut order, 0, &mut |order| {
+            let slots: Vec<i64> = order.iter().map(|idx| values[*idx]).collect();
+            if found.is_none() && self.evaluate(&slots) == self.result {
+                found = Some(order.to_vec());
+            }
+        });
+        found
+    }
+}
+
+fn permute(items: &mut [usize], start: usize, visit: &mut impl FnMut(&[usize])) {
+    if start == items.len() {
+        visit(items);
+        return;
+    }
+    for idx in start..items.len() {
+        items.swap(start, idx);
+        permute(items, start + 1, visit);
+        items.swap(start, idx);
+    }
+}
+
+/// Value shown on a coin, e.g. `It has seven dots on one side.` or `It has a pentagon ...`.
+pub fn coin_value(description: &str) -> Option<i64> {
+    let (_, rest) = description.split_once("It has ")?;
+    let (marking, _) = rest.split_once(" on one side")?;
+    if let Some(count) = marking
+        .strip_suffix(" dots")
+        .or(marking.strip_suffix(" dot"))
+    {
+        return NUMBER_WORDS
+            .iter()
+            .position(|word| *word == count)
+            .map(|value| value as i64);
+    }
+    let shape = marking.strip_prefix("a ").or(marking.strip_prefix("an "))?;
+    SHAPES
+        .iter()
+        .find(|(name, _)| *name == shape)
+        .map(|(_, value)| *value)
+}
+
+/// Sends a command and returns the game state parsed from its output.
+fn ask(vm: &mut VirtualMachine, command: &str) -> GameState {
+    vm.prefeed_stdin(&format!("{}\n", command));
+    GameState::from_output(&vm.run_until_input().output)
+}
+
+/// Reads the equation of the current room and the coins of the inventory from the game, then
+/// queues the `use` commands placing the coins in the solving order.
+///
+/// The machine has to wait at the prompt, the returned commands are not executed yet. The
+/// game is asked on a copy of the machine, so only the `use` commands end up in its input
+/// history, transcript and output.
+pub fn solve(vm: &mut VirtualMachine) -> Result<Vec<String>, CoinError> {
+    let mut probe = VirtualMachine::builder().build();
+    probe.apply_state(vm.get_state());
+    probe.breakpoints = Default::default();
+    probe.paused = false;
+
+    let equation = ask(&mut probe, "look")
+        .room
+        .and_then(|room| Equation::find(&room.description))
+        .ok_or(CoinError::NoEquation)?;
+    let coins: Vec<String> = ask(&mut probe, "inv")
+        .inventory
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|item| item.ends_with(" coin"))
+        .collect();
+    if coins.len() < equation.open_slots() {
+        return Err(CoinError::NotEnoughCoins(
+            coins.len(),
+            equation.open_slots(),
+        ));
+    }
+
+    let mut values = vec![];
+    for coin in coins.iter() {
+        let description = ask(&mut probe, &format!("look {}", coin)).last_message;
+        let value = description
+            .as_deref()
+            .and_then(coin_value)
+            .ok_or_else(|| CoinError::UnknownCoin(coin.clone()))?;
+        values.push(value);
+    }
+
+    // with spare coins every selection of them has to be tried
+    let order = equation.solve(&values).ok_or(CoinError::NoSolution)?;
+    let commands: Vec<String> = order
+        .into_iter()
+        .take(equation.open_slots())
+        .map(|idx| format!("use {}", coins[idx]))
+        .collect();
+    for command in commands.iter() {
+        vm.prefeed_stdin(&format!("{}\n", command));
+    }
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        transform_bytes_to_program_code,
+        vm::{
+            replay::{Replay, ReplayRunner, ReplayStatus},
+            VirtualMachine,
+        },
+    };
+
+    use super::{coin_value, solve, Equation, Operand};
+
+    #[test]
+    fn test_solve_equation() {
+        let equation = Equation::parse("_ + _ * _^2 + _^3 - _ = 399").unwrap();
+        assert_eq!(equation.open_slots(), 5);
+        assert_eq!(equation.evaluate(&[9, 2, 5, 7, 3]), 399);
+        let values = [2, 3, 5, 7, 9];
+        let order = equation.solve(&values).unwrap();
+        assert_eq!(
+            order.iter().map(|idx| values[*idx]).collect::<Vec<_>>(),
+            vec![9, 2, 5, 7, 3]
+        );
+
+        let partial = Equation::parse("9 + 2 * _^2 + _^3 - _ = 399").unwrap();
+        assert_eq!(partial.terms[0].operand, Operand::Value(9));
+        assert_eq!(partial.open_slots(), 3);
+        assert_eq!(partial.evaluate(&[5, 7, 3]), 399);
+
+        assert_eq!(
+            coin_value("This coin is made of a red metal.  It has two dots on one side."),
+            Some(2)
+        );
+        assert_eq!(
+            coin_value("This coin is somewhat corroded.  It has a triangle on one side."),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn test_solve_records_only_use_commands() {
+        let program =
+            transform_bytes_to_program_code(include_bytes!("../../payloads/challenge.bin"))
+                .unwrap();
+        let mut vm = VirtualMachine::builder().program(&program).build();
+        let replay = include_str!("../../replays/synacor-hq.txt");
+        let (commands, _) = replay.split_once("#solve coins").unwrap();
+        let mut runner = ReplayRunner::new(Replay::parse(commands).unwrap());
+        assert_eq!(runner.run(&mut vm), Ok(ReplayStatus::Finished));
+        let history = vm.stdin_history.clone();
+
+        let placed = solve(&mut vm).unwrap();
+        assert_eq!(placed.len(), 5);
+        let expected: String = placed
+            .iter()
+            .map(|command| format!("{}\n", command))
+            .collect();
+        assert!(vm
+            .run_until_input()
+            .output
+            .contains("you hear a click from the north door"));
+        assert_eq!(vm.stdin_history, format!("{}{}", history, expected));
+    }
+}
@@ -0,0 +1,350 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet, VecDeque},
+    fmt::Write,
+    hash::{Hash, Hasher},
+};
+
+use serde_json::{json, Value};
+
+use crate::vm::{execution::StopReason, VirtualMachine, VirtualMachineSavestate};
+
+use super::parser::{GameState, Room};
+
+/// Upper bound of distinct rooms, some rooms change memory on every visit.
+pub const DEFAULT_MAX_ROOMS: usize = 500;
+
+const MAP_TEMPLATE: &str = include_str!("../../docs/map.json");
+const ROOM_SPACING_X: i64 = 160;
+const ROOM_SPACING_Y: i64 = 128;
+
+#[derive(Debug, Clone)]
+pub struct ExploredRoom {
+    pub id: usize,
+    pub room: Room,
+    /// Hash of the heap when the room was entered.
+    pub memory_hash: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Passage {
+    pub from: usize,
+    pub to: usize,
+    pub exit: String,
+}
+
+#[derive(Debug, Default)]
+pub struct WorldMap {
+    pub rooms: Vec<ExploredRoom>,
+    pub passages: Vec<Passage>,
+    /// Exits which ended the game or did not lead into a room.
+    pub dead_ends: Vec<(usize, String, String)>,
+}
+
+/// Commands that do not change the game but leave different text in its line buffer.
+const CALIBRATION_COMMANDS: [&str; 3] = [
+    "look\n",
+    "inv\n",
+    "zzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzz\n",
+];
+
+/// Heap cells that change with the typed text only, e.g. the game's line buffer.
+fn volatile_cells(vm: &mut VirtualMachine) -> HashSet<usize> {
+    let savestate = vm.get_state();
+    let heaps: Vec<_> = CALIBRATION_COMMANDS
+        .iter()
+        .map(|command| {
+            vm.apply_state(savestate.clone());
+            vm.prefeed_stdin(command);
+            vm.run_until_input();
+            vm.memory.heap
+        })
+        .collect();
+    vm.apply_state(savestate);
+
+    (0..heaps[0].len())
+        .filter(|idx| heaps.iter().any(|heap| heap[*idx] != heaps[0][*idx]))
+        .collect()
+}
+
+fn memory_hash(vm: &VirtualMachine, volatile: &HashSet<usize>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for (idx, value) in vm.memory.heap.iter().enumerate() {
+        if !volatile.contains(&idx) {
+            value.hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+/// Breadth-first visits every exit of every room, starting at the machine's current state.
+///
+/// Rooms are told apart by their title, description and heap (minus the cells holding the
+/// typed command), so identical looking rooms like the twisty passages get separate entries.
+pub fn explore(vm: &mut VirtualMachine, max_rooms: usize) -> WorldMap {
+    let mut map = WorldMap::default();
+    let mut known: HashMap<(String, String, u64), usize> = HashMap::new();
+    let mut queue: VecDeque<(usize, VirtualMachineSavestate)> = VecDeque::new();
+
+    let outcome = vm.run_until_input();
+    let mut state = GameState::from_output(&outcome.output);
+    if state.room.is_none() {
+        vm.prefeed_stdin("look\n");
+        state = GameState::from_output(&vm.run_until_input().output);
+    }
+    let Some(room) = state.room else {
+        return map;
+    };
+    let volatile = volatile_cells(vm);
+    let hash = memory_hash(vm, &volatile);
+    known.insert((room.title.clone(), room.description.clone(), hash), 0);
+    map.rooms.push(ExploredRoom {
+        id: 0,
+        room,
+        memory_hash: hash,
+    });
+    queue.push_back((0, vm.get_state()));
+
+    while let Some((id, savestate)) = queue.pop_front() {
+        for exit in map.rooms[id].room.exits.clone() {
+            vm.apply_state(savestate.clone());
+            vm.prefeed_stdin(&format!("{}\n", exit));
+            let outcome = vm.run_until_input();
+
+            let parsed = GameState::from_output(&outcome.output);
+            let room = match (outcome.reason, parsed.room) {
+                (StopReason::AwaitingInput, Some(room)) => room,
+                (_, _) => {
+                    let message = parsed.last_message.unwrap_or_default();
+                    map.dead_ends.push((id, exit, message));
+                    continue;
+                }
+            };
+
+            let hash = memory_hash(vm, &volatile);
+            let key = (room.title.clone(), room.description.clone(), hash);
+            let target = match known.get(&key) {
+                Some(target) => *target,
+                None if map.rooms.len() >= max_rooms => continue,
+                None => {
+                    let target = map.rooms.len();
+                    known.insert(key, target);
+                    map.rooms.push(ExploredRoom {
+                        id: target,
+                        room,
+                        memory_hash: hash,
+                    });
+                    queue.push_back((target, vm.get_state()));
+                    target
+                }
+            };
+            map.passages.push(Passage {
+                from: id,
+                to: target,
+                exit,
+            });
+        }
+    }
+
+    map
+}
+
+/// Compass index used by the map editor, 0 is north and 8 is south.
+fn exit_direction(exit: &str) -> Option<(i64, i64, u8)> {
+    match exit {
+        "north" => Some((0, -1, 0)),
+        "east" => Some((1, 0, 4)),
+        "south" => Some((0, 1, 8)),
+        "west" => Some((-1, 0, 12)),
+        "up" => Some((1, -1, 2)),
+        "down" => Some((-1, 1, 10)),
+        _ => None,
+    }
+}
+
+fn opposite_direction(direction: u8) -> u8 {
+    (direction + 8) % 16
+}
+
+fn guid(kind: u64, id: usize) -> String {
+    let mut hasher = DefaultHasher::new();
+    (kind, id).hash(&mut hasher);
+    let high = hasher.finish();
+    id.hash(&mut hasher);
+    let low = hasher.finish();
+    format!(
+        "{:08x}-{:04x}-4{:03x}-8{:03x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xfff,
+        low >> 52,
+        low & 0xffff_ffff_ffff
+    )
+}
+
+impl WorldMap {
+    /// Grid positions following the exit directions, collisions move further east.
+    fn layout(&self) -> Vec<(i64, i64)> {
+        let mut positions: BTreeMap<usize, (i64, i64)> = BTreeMap::new();
+        let mut occupied: HashSet<(i64, i64)> = HashSet::new();
+        for room in self.rooms.iter() {
+            if positions.contains_key(&room.id) {
+                continue;
+            }
+            let mut position = (0, positions.len() as i64);
+            if let Some(passage) = self
+                .passages
+                .iter()
+                .find(|passage| passage.to == room.id && positions.contains_key(&passage.from))
+            {
+                let (x, y) = positions[&passage.from];
+                let (dx, dy, _) = exit_direction(&passage.exit).unwrap_or((1, 1, 0));
+                position = (x + dx, y + dy);
+            }
+            while occupied.contains(&position) {
+                position.0 += 1;
+            }
+            occupied.insert(position);
+            positions.insert(room.id, position);
+        }
+        self.rooms.iter().map(|room| positions[&room.id]).collect()
+    }
+
+    /// Exports the map in the format of `docs/map.json`.
+    pub fn to_json(&self) -> Value {
+        let template: Value = serde_json::from_str(MAP_TEMPLATE).unwrap_or_default();
+        let positions = self.layout();
+        let mut elements = vec![];
+
+        for (room, (x, y)) in self.rooms.iter().zip(positions.iter()) {
+            elements.push(json!({
+                "id": room.id + 1,
+                "_guid": guid(0, room.id),
+                "_x": x * ROOM_SPACING_X,
+                "_y": y * ROOM_SPACING_Y,
+                "_type": "Room",
+                "_name": room.room.title,
+                "_subtitle": "",
+                "_description": room.room.description,
+                "_dark": false,
+                "_endroom": false,
+                "_w": 96,
+                "_h": 64,
+                "objects": room.room.things.iter().enumerate().map(|(idx, thing)| json!({
+                    "id": 0,
+                    "_guid": guid(1, room.id * 64 + idx),
+                    "_name": thing,
+                    "_type": "Object",
+                    "_description": "",
+                    "_kind": 5,
+                    "_content": [],
+                })).collect::<Vec<_>>(),
+                "_lineStyle": 1,
+                "_shape": 0,
+            }));
+        }
+
+        let mut drawn: HashSet<(usize, usize)> = HashSet::new();
+        for (idx, passage) in self.passages.iter().enumerate() {
+            let reverse = self
+                .passages
+                .iter()
+                .any(|other| other.from == passage.to && other.to == passage.from);
+            // a two-way passage is drawn once
+            if reverse && drawn.contains(&(passage.to, passage.from)) {
+                continue;
+            }
+            drawn.insert((passage.from, passage.to));
+
+            let start_direction = exit_direction(&passage.exit).map_or(0, |(_, _, d)| d);
+            elements.push(json!({
+                "id": self.rooms.len() + idx + 1,
+                "_guid": guid(2, idx),
+                "_type": "Connector",
+                "_name": passage.exit,
+                "_dockStart": passage.from + 1,
+                "_dockEnd": passage.to + 1,
+                "_startDir": start_direction,
+                "_endDir": opposite_direction(start_direction),
+                "_startY": 0,
+                "_startX": 0,
+                "_endY": 0,
+                "_endX": 0,
+                "_oneWay": !reverse,
+                "_startType": 0,
+                "_endType": 0,
+                "_startLabel": passage.exit,
+                "_endLabel": "",
+                "_lineStyle": 1,
+            }));
+        }
+
+        json!({
+            "settings": template.get("settings").cloned().unwrap_or_default(),
+            "title": "",
+            "author": "",
+            "description": "",
+            "elements": elements,
+            "startRoom": 0,
+        })
+    }
+
+    /// Exports the map in Graphviz DOT format.
+    pub fn to_dot(&self) -> String {
+        let mut text = String::from("digraph map {\n    node [shape=box];\n");
+        for room in self.rooms.iter() {
+            writeln!(
+                text,
+                "    room_{} [label=\"{}\"];",
+                room.id,
+                room.room.title.replace('"', "\\\"")
+            )
+            .unwrap();
+        }
+        for passage in self.passages.iter() {
+            writeln!(
+                text,
+                "    room_{} -> room_{} [label=\"{}\"];",
+                passage.from,
+                passage.to,
+                passage.exit.replace('"', "\\\"")
+            )
+            .unwrap();
+        }
+        text.push_str("}\n");
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{transform_bytes_to_program_code, vm::VirtualMachine};
+
+    use super::explore;
+
+    #[test]
+    fn test_explore_from_start() {
+        let program =
+            transform_bytes_to_program_code(include_bytes!("../../payloads/challenge.bin"))
+                .unwrap();
+        let mut vm = VirtualMachine::builder().program(&program).build();
+        let map = explore(&mut vm, 100);
+
+        assert_eq!(map.rooms[0].room.title, "Foothills");
+        let foothills = map
+            .rooms
+            .iter()
+            .filter(|room| room.room.description == map.rooms[0].room.description)
+            .count();
+        assert_eq!(foothills, 1);
+        assert!(map
+            .passages
+            .iter()
+            .any(|passage| passage.from == 0 && passage.exit == "doorway"));
+
+        let json = map.to_json();
+        assert!(json["settings"].is_object());
+        let elements = json["elements"].as_array().unwrap().len();
+        assert!(elements > map.rooms.len());
+        assert!(elements < map.rooms.len() + map.passages.len());
+    }
+}
@@ -0,0 +1,27 @@
+use crate::vm::VirtualMachine;
+
+/// Solver for the coin equation on the monument.
+pub mod coins;
+
+/// Breadth-first world map explorer.
+pub mod explorer;
+
+/// Teleporter confirmation solver and call site patch.
+pub mod teleporter;
+
+/// Parser turning the adventure output into rooms, inventories and prompts.
+pub mod parser;
+
+/// Shortest orb walk through the vault lock grid.
+pub mod vault;
+
+/// Solver for the `#solve <puzzle>` directive of replays, `#solve coins` places the coins on
+/// the monument.
+pub fn solve_puzzle(vm: &mut VirtualMachine, puzzle: &str) -> Result<(), String> {
+    match puzzle {
+        "coins" => coins::solve(vm)
+            .map(|_| ())
+            .map_err(|error| error.to_string()),
+        _ => Err(format!("no solver for the puzzle '{}'", puzzle)),
+    }
+}
@@ -0,0 +1,302 @@
+pub const PROMPT: &str = "What do you do?";
+const THINGS_HEADER: &str = "Things of interest here:";
+const INVENTORY_HEADER: &str = "Your inventory:";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Room {
+    pub title: String,
+    pub description: String,
+    pub things: Vec<String>,
+    pub exits: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameEvent {
+    Room(Room),
+    Inventory(Vec<String>),
+    /// Any other paragraph, e.g. the reply to `take` or `use`.
+    Message(String),
+    /// A command echoed into the output by the viewer.
+    Command(String),
+    Prompt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListKind {
+    Things,
+    Exits,
+    Inventory,
+}
+
+#[derive(Debug, Default)]
+struct Parser {
+    events: Vec<GameEvent>,
+    room: Option<Room>,
+    in_description: bool,
+    /// A blank line was seen in the description, which may continue with another paragraph.
+    description_break: bool,
+    list: Option<(ListKind, Vec<String>)>,
+    paragraph: Vec<String>,
+    after_prompt: bool,
+}
+
+impl Parser {
+    fn finish_list(&mut self) {
+        let Some((kind, items)) = self.list.take() else {
+            return;
+        };
+        match (kind, &mut self.room) {
+            (ListKind::Things, Some(room)) => room.things = items,
+            (ListKind::Exits, Some(room)) => room.exits = items,
+            (ListKind::Inventory, _) => self.events.push(GameEvent::Inventory(items)),
+            // lists outside of a room description are kept as plain text
+            (_, None) => self.events.push(GameEvent::Message(items.join("\n"))),
+        }
+    }
+
+    fn finish_paragraph(&mut self) {
+        if !self.paragraph.is_empty() {
+            let text = self.paragraph.join("\n");
+            self.paragraph.clear();
+            self.events.push(GameEvent::Message(text));
+        }
+    }
+
+    fn finish_room(&mut self) {
+        self.finish_list();
+        if let Some(room) = self.room.take() {
+            self.events.push(GameEvent::Room(room));
+        }
+        self.in_description = false;
+    }
+
+    fn finish_all(&mut self) {
+        self.finish_paragraph();
+        self.finish_room();
+    }
+
+    fn line(&mut self, line: &str) {
+        let line = line.trim_end();
+        let after_prompt = std::mem::take(&mut self.after_prompt);
+
+        if line.is_empty() {
+            self.finish_paragraph();
+            // descriptions can have several paragraphs, but end with the first list
+            if self.list.is_some() {
+                self.finish_list();
+                self.in_description = false;
+            } else if self.in_description {
+                self.description_break = true;
+            }
+            return;
+        }
+
+        if line == PROMPT {
+            self.finish_all();
+            self.events.push(GameEvent::Prompt);
+            self.after_prompt = true;
+            return;
+        }
+
+        if after_prompt {
+            self.events.push(GameEvent::Command(line.to_string()));
+            return;
+        }
+
+        if let Some(item) = line.strip_prefix("- ") {
+            if let Some((_, items)) = &mut self.list {
+                items.push(item.to_string());
+                return;
+            }
+        }
+
+        if let Some(title) = line
+            .strip_prefix("== ")
+            .and_then(|rest| rest.strip_suffix(" =="))
+        {
+            self.finish_all();
+            self.room = Some(Room {
+                title: title.to_string(),
+                ..Default::default()
+            });
+            self.in_description = true;
+            self.description_break = false;
+            return;
+        }
+
+        let list_kind = if line == THINGS_HEADER {
+            Some(ListKind::Things)
+        } else if line == INVENTORY_HEADER {
+            Some(ListKind::Inventory)
+        } else if line.starts_with("There is") && line.ends_with("exit:")
+            || line.starts_with("There are") && line.ends_with("exits:")
+        {
+            Some(ListKind::Exits)
+        } else {
+            None
+        };
+        if let Some(kind) = list_kind {
+            self.finish_paragraph();
+            if kind == ListKind::Inventory {
+                self.finish_room();
+            }
+            self.list = Some((kind, vec![]));
+            self.description_break = false;
+            return;
+        }
+
+        match &mut self.room {
+            Some(room) if self.in_description => {
+                if !room.description.is_empty() {
+                    room.description.push('\n');
+                }
+                if std::mem::take(&mut self.description_break) {
+                    room.description.push('\n');
+                }
+                room.description.push_str(line);
+            }
+            _ => {
+                self.finish_room();
+                self.paragraph.push(line.to_string());
+            }
+        }
+    }
+}
+
+/// Splits game output into typed events, an incomplete trailing line is ignored.
+pub fn parse(output: &str) -> Vec<GameEvent> {
+    let mut parser = Parser::default();
+    let complete = match output.rfind('\n') {
+        Some(position) => &output[..position],
+        None => "",
+    };
+    for line in complete.lines() {
+        parser.line(line);
+    }
+    parser.finish_all();
+    parser.events
+}
+
+/// Knowledge about the game accumulated from its output.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GameState {
+    pub room: Option<Room>,
+    /// Last inventory listing, `None` until `inv` was used.
+    pub inventory: Option<Vec<String>>,
+    pub last_message: Option<String>,
+    pub last_command: Option<String>,
+    pub awaiting_command: bool,
+}
+
+impl GameState {
+    pub fn from_output(output: &str) -> Self {
+        let mut state = Self::default();
+        for event in parse(output) {
+            state.apply(event);
+        }
+        state
+    }
+
+    pub fn apply(&mut self, event: GameEvent) {
+        self.awaiting_command = false;
+        match event {
+            GameEvent::Room(room) => {
+                self.room = Some(room);
+                self.last_message = None;
+            }
+            GameEvent::Inventory(items) => self.inventory = Some(items),
+            GameEvent::Message(text) => self.last_message = Some(text),
+            GameEvent::Command(command) => self.last_command = Some(command),
+            GameEvent::Prompt => self.awaiting_command = true,
+        }
+    }
+
+    pub fn can_go(&self, exit: &str) -> bool {
+        self.room
+            .as_ref()
+            .is_some_and(|room| room.exits.iter().any(|e| e == exit))
+    }
+
+    pub fn has_item(&self, item: &str) -> bool {
+        self.inventory
+            .as_ref()
+            .is_some_and(|items| items.iter().any(|i| i == item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, GameEvent, GameState, Room};
+
+    const OUTPUT: &str = "== Foothills ==
+You find yourself standing at the base of an enormous mountain.  
+
+Things of interest here:
+- tablet
+
+There are 2 exits:
+- doorway
+- south
+
+What do you do?
+take tablet
+
+
+Taken.
+
+What do you do?
+
+
+Your inventory:
+- tablet
+
+What do you do?
+";
+
+    #[test]
+    fn test_parse_events() {
+        let room = Room {
+            title: "Foothills".to_string(),
+            description: "You find yourself standing at the base of an enormous mountain."
+                .to_string(),
+            things: vec!["tablet".to_string()],
+            exits: vec!["doorway".to_string(), "south".to_string()],
+        };
+        assert_eq!(
+            parse(OUTPUT),
+            vec![
+                GameEvent::Room(room),
+                GameEvent::Prompt,
+                GameEvent::Command("take tablet".to_string()),
+                GameEvent::Message("Taken.".to_string()),
+                GameEvent::Prompt,
+                GameEvent::Inventory(vec!["tablet".to_string()]),
+                GameEvent::Prompt,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_game_state() {
+        let state = GameState::from_output(OUTPUT);
+        assert!(state.awaiting_command);
+        assert!(state.can_go("doorway"));
+        assert!(!state.can_go("north"));
+        assert!(state.has_item("tablet"));
+        assert_eq!(state.last_command.as_deref(), Some("take tablet"));
+
+        // the unfinished description line is not parsed yet
+        let partial = GameState::from_output("== Dark cave ==\nThis seems");
+        let room = partial.room.unwrap();
+        assert_eq!(room.title, "Dark cave");
+        assert!(room.description.is_empty());
+
+        // the description continues after a blank line until the first list
+        let ruins = GameState::from_output(
+            "== Ruins ==\nIt reads:\n\n_ + _ = 3\n\nThere is 1 exit:\n- north\n\nWhat do you do?\n",
+        );
+        let room = ruins.room.unwrap();
+        assert_eq!(room.description, "It reads:\n\n_ + _ = 3");
+        assert_eq!(room.exits, vec!["north".to_string()]);
+    }
+}
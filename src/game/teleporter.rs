@@ -0,0 +1,213 @@
+use std::{num::NonZeroUsize, ops::Range, thread};
+
+use crate::vm::{
+    memory::{MAX_ADDRESS, REGISTER_ADDRESS_START},
+    opcodes::Instruction,
+    VirtualMachine,
+};
+
+/// Values are 15 bit, every calculation of the program wraps around at this.
+const MODULUS: usize = MAX_ADDRESS as usize + 1;
+const R0: u16 = REGISTER_ADDRESS_START;
+const R1: u16 = REGISTER_ADDRESS_START + 1;
+const R7: usize = 7;
+const NOOP_OPCODE: u16 = 21;
+
+/// Arguments the confirmation function is called with and the result the teleporter expects.
+pub const CONFIRMATION_ARGUMENTS: (u16, u16) = (4, 1);
+pub const EXPECTED_RESULT: u16 = 6;
+
+/// Native version of the recursive function the teleporter calls, see `docs/disassembly`:
+/// `f(0, b) = b + 1`, `f(a, 0) = f(a - 1, r7)` and `f(a, b) = f(a - 1, f(a, b - 1))`.
+///
+/// Every level of `a` only depends on the one below, so instead of recursing the function is
+/// tabulated for all `b` one level at a time.
+pub fn evaluate(a: u16, b: u16, r7: u16) -> u16 {
+    let mut row = vec![0; MODULUS];
+    let mut next = vec![0; MODULUS];
+    evaluate_with(a, b, r7, &mut row, &mut next)
+}
+
+/// `evaluate` reusing the given tables, which have to hold `MODULUS` values.
+fn evaluate_with<'t>(
+    a: u16,
+    b: u16,
+    r7: u16,
+    mut row: &'t mut [u16],
+    mut next: &'t mut [u16],
+) -> u16 {
+    for (idx, value) in row.iter_mut().enumerate() {
+        *value = ((idx + 1) % MODULUS) as u16;
+    }
+    for level in 1..=a {
+        // only the requested value is needed of the last level
+        let end = if level == a { b as usize + 1 } else { MODULUS };
+        next[0] = row[r7 as usize];
+        for idx in 1..end {
+            next[idx] = row[next[idx - 1] as usize];
+        }
+        std::mem::swap(&mut row, &mut next);
+    }
+    row[b as usize]
+}
+
+/// Tries every non-zero eighth register value, spread over the given amount of threads
+/// (0 uses all cores), and returns the ones passing the confirmation.
+pub fn solve(threads: usize) -> Vec<u16> {
+    solve_range(1..MODULUS as u16, threads)
+}
+
+/// `solve` over the given candidates only.
+pub fn solve_range(candidates: Range<u16>, threads: usize) -> Vec<u16> {
+    let threads = match threads {
+        0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        threads => threads,
+    };
+    let candidates: Vec<u16> = candidates.collect();
+    if candidates.is_empty() {
+        return vec![];
+    }
+    let chunk_size = candidates.len().div_ceil(threads);
+    let (a, b) = CONFIRMATION_ARGUMENTS;
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = candidates
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut row = vec![0; MODULUS];
+                    let mut next = vec![0; MODULUS];
+                    chunk
+                        .iter()
+                        .copied()
+                        .filter(|r7| {
+                            evaluate_with(a, b, *r7, &mut row, &mut next) == EXPECTED_RESULT
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("Solver thread panicked"))
+            .collect()
+    })
+}
+
+/// Location of the confirmation call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallSite {
+    /// Address of `SET r0 4`, followed by `SET r1 1`.
+    pub setup: u16,
+    /// Address of the `CALL` to the confirmation function.
+    pub call: u16,
+    pub function: u16,
+}
+
+/// Searches the heap for `SET r0 4`, `SET r1 1`, `CALL f` and a comparison of `r0` with 6.
+pub fn find_call_site(heap: &[u16]) -> Option<CallSite> {
+    let (a, b) = CONFIRMATION_ARGUMENTS;
+    let decode_at = |address: usize| {
+        let end = (address + 4).min(heap.len());
+        Instruction::try_from(&heap[address..end]).ok()
+    };
+
+    (0..heap.len()).find_map(|setup| {
+        let mut address = setup;
+        let mut instructions = vec![];
+        for _ in 0..4 {
+            let instruction = decode_at(address)?;
+            address += instruction.byte_length();
+            instructions.push(instruction);
+            if address >= heap.len() {
+                break;
+            }
+        }
+        use Instruction::{Call, Equality, Set};
+        match instructions[..] {
+            [Set(R0, first), Set(R1, second), Call(function), Equality(_, R0, EXPECTED_RESULT)]
+                if (first, second) == (a, b) =>
+            {
+                Some(CallSite {
+                    setup: setup as u16,
+                    call: setup as u16 + 6,
+                    function,
+                })
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Writes that set the eighth register and replace the confirmation call with its result, as
+/// `(address, value)` pairs for heap cells and registers.
+pub fn patch_writes(heap: &[u16], r7: u16) -> Option<(CallSite, Vec<(u16, u16)>)> {
+    let call_site = find_call_site(heap)?;
+    // `SET r0 4` becomes `SET r0 6`, `CALL f` two `NOOP`s
+    let writes = vec![
+        (REGISTER_ADDRESS_START + R7 as u16, r7),
+        (call_site.setup + 2, EXPECTED_RESULT),
+        (call_site.call, NOOP_OPCODE),
+        (call_site.call + 1, NOOP_OPCODE),
+    ];
+    Some((call_site, writes))
+}
+
+/// Sets the eighth register and replaces the confirmation call with its result, so using the
+/// teleporter no longer takes forever.
+pub fn patch(vm: &mut VirtualMachine, r7: u16) -> Option<CallSite> {
+    let (call_site, writes) = patch_writes(&vm.memory.heap, r7)?;
+    for (address, value) in writes {
+        vm.memory.poke(&address, value).ok()?;
+    }
+    Some(call_site)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{transform_bytes_to_program_code, vm::VirtualMachine};
+
+    use super::{evaluate, find_call_site, patch, solve_range, CallSite, EXPECTED_RESULT};
+
+    #[test]
+    fn test_evaluate() {
+        // with r7 = 1 the function is the Ackermann function
+        assert_eq!(evaluate(0, 5, 1), 6);
+        assert_eq!(evaluate(1, 2, 1), 4);
+        assert_eq!(evaluate(2, 3, 1), 9);
+        assert_eq!(evaluate(3, 3, 1), 61);
+        // A(4, 1) = 65533, wrapped to 15 bit
+        assert_eq!(evaluate(4, 1, 1), 65533 % 32768);
+
+        // the value the teleporter is looking for
+        assert_eq!(evaluate(4, 1, 25734), EXPECTED_RESULT);
+        assert_ne!(evaluate(4, 1, 25733), EXPECTED_RESULT);
+    }
+
+    #[test]
+    fn test_solve_range() {
+        assert_eq!(solve_range(25700..25760, 3), vec![25734]);
+        // more threads than candidates, every chunk holds a single value
+        assert_eq!(solve_range(25730..25737, 16), vec![25734]);
+        assert!(solve_range(1..40, 2).is_empty());
+        assert!(solve_range(25734..25734, 4).is_empty());
+    }
+
+    #[test]
+    fn test_patch_call_site() {
+        let program =
+            transform_bytes_to_program_code(include_bytes!("../../payloads/challenge.bin"))
+                .unwrap();
+        let mut vm = VirtualMachine::builder().program(&program).build();
+
+        let expected = CallSite {
+            setup: 5505,
+            call: 5511,
+            function: 6049,
+        };
+        assert_eq!(find_call_site(&vm.memory.heap), Some(expected));
+        assert_eq!(patch(&mut vm, 1234), Some(expected));
+        assert_eq!(vm.memory.registers[7], 1234);
+        assert_eq!(find_call_site(&vm.memory.heap), None);
+    }
+}
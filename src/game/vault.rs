@@ -0,0 +1,271 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+};
+
+use crate::vm::{memory::MAX_ADDRESS, VirtualMachine};
+
+use super::parser::{GameState, Room};
+
+/// Rooms per side of the grid, the antechamber is the south-west and the door the north-east one.
+pub const SIZE: usize = 4;
+const START: (usize, usize) = (SIZE - 1, 0);
+const DOOR: (usize, usize) = (0, SIZE - 1);
+const ANTECHAMBER_TITLE: &str = "Vault Antechamber";
+const MOSAIC_MARKER: &str = "mosaic depicting ";
+/// Printed when the orb arrives at the door with the right weight.
+const DOOR_UNLOCKED: &str = "You hear a click from the vault door.";
+/// A room of the grid and the orb's weight on arrival.
+type State = ((usize, usize), i64);
+
+/// Row and column offsets of the exits, row 0 is the northern one.
+const DIRECTIONS: [(&str, isize, isize); 4] = [
+    ("north", -1, 0),
+    ("east", 0, 1),
+    ("south", 1, 0),
+    ("west", 0, -1),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VaultError {
+    NotInAntechamber,
+    /// The room at the row and column does not show a number or operator.
+    UnreadableRoom(usize, usize),
+    NoPath,
+}
+
+impl Display for VaultError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotInAntechamber => write!(f, "not in the {}", ANTECHAMBER_TITLE),
+            Self::UnreadableRoom(row, column) => {
+                write!(f, "room at row {} column {} has no mosaic", row, column)
+            }
+            Self::NoPath => write!(f, "no walk reaches the door with the right weight"),
+        }
+    }
+}
+
+impl std::error::Error for VaultError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tile {
+    Number(i64),
+    /// `+`, `-` or `*`, applied with the number of the next room.
+    Operator(char),
+}
+
+impl Tile {
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "+" | "-" | "*" => text.chars().next().map(Self::Operator),
+            number => number.parse::<i64>().ok().map(Self::Number),
+        }
+    }
+}
+
+/// Rooms of the vault lock, the orb starts with the antechamber's number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid {
+    /// Rows from north to south.
+    pub tiles: [[Tile; SIZE]; SIZE],
+    /// Weight the door asks for.
+    pub target: i64,
+}
+
+impl Grid {
+    fn start_value(&self) -> i64 {
+        match self.tiles[START.0][START.1] {
+            Tile::Number(value) => value,
+            Tile::Operator(_) => 0,
+        }
+    }
+
+    fn neighbour(&self, (row, column): (usize, usize), direction: usize) -> Option<(usize, usize)> {
+        let (_, row_offset, column_offset) = DIRECTIONS[direction];
+        let row = row
+            .checked_add_signed(row_offset)
+            .filter(|row| *row < SIZE)?;
+        let column = column
+            .checked_add_signed(column_offset)
+            .filter(|column| *column < SIZE)?;
+        Some((row, column))
+    }
+
+    /// Shortest walk from the antechamber to the door arriving with the target weight.
+    ///
+    /// The rooms alternate between numbers and operators, so the search steps two rooms at a
+    /// time over (room, weight) states. Walking back into the antechamber resets the orb and
+    /// the door consumes it, neither is allowed halfway. Weights outside of 15 bit are pruned.
+    pub fn solve(&self) -> Option<Vec<&'static str>> {
+        let start = (START, self.start_value());
+        let mut previous: HashMap<State, (State, [usize; 2])> = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+
+        while let Some((room, weight)) = queue.pop_front() {
+            for first in 0..DIRECTIONS.len() {
+                let Some(middle) = self.neighbour(room, first) else {
+                    continue;
+                };
+                let Tile::Operator(operator) = self.tiles[middle.0][middle.1] else {
+                    continue;
+                };
+                for second in 0..DIRECTIONS.len() {
+                    let Some(next) = self.neighbour(middle, second) else {
+                        continue;
+                    };
+                    let Tile::Number(number) = self.tiles[next.0][next.1] else {
+                        continue;
+                    };
+                    let next_weight = match operator {
+                        '+' => weight + number,
+                        '-' => weight - number,
+                        _ => weight * number,
+                    };
+                    let state = (next, next_weight);
+                    if next == START
+                        || !(0..=MAX_ADDRESS as i64).contains(&next_weight)
+                        || previous.contains_key(&state)
+                    {
+                        continue;
+                    }
+                    previous.insert(state, ((room, weight), [first, second]));
+                    if next == DOOR {
+                        if next_weight == self.target {
+                            return Some(walk_to(&previous, start, state));
+                        }
+                        continue;
+                    }
+                    queue.push_back(state);
+                }
+            }
+        }
+        None
+    }
+}
+
+fn walk_to(
+    previous: &HashMap<State, (State, [usize; 2])>,
+    start: State,
+    mut state: State,
+) -> Vec<&'static str> {
+    let mut directions = vec![];
+    while state != start {
+        let (before, [first, second]) = previous[&state];
+        directions.push(DIRECTIONS[second].0);
+        directions.push(DIRECTIONS[first].0);
+        state = before;
+    }
+    directions.reverse();
+    directions
+}
+
+/// First `'`-quoted word of the text, e.g. the `22` of `the number '22' is carved`.
+fn quoted(text: &str) -> Option<&str> {
+    let (_, rest) = text.split_once('\'')?;
+    rest.split_once('\'').map(|(word, _)| word)
+}
+
+fn mosaic(room: &Room) -> Option<Tile> {
+    let (_, rest) = room.description.split_once(MOSAIC_MARKER)?;
+    quoted(rest).and_then(Tile::parse)
+}
+
+fn look(vm: &mut VirtualMachine) -> Option<Room> {
+    vm.prefeed_stdin("look\n");
+    GameState::from_output(&vm.run_until_input().output).room
+}
+
+/// Reads the grid from the room descriptions, walking east and then north from the
+/// antechamber to every room. The machine is restored to its state after every walk, so the
+/// orb may already be taken.
+pub fn read_grid(vm: &mut VirtualMachine) -> Result<Grid, VaultError> {
+    let antechamber = look(vm)
+        .filter(|room| room.title == ANTECHAMBER_TITLE)
+        .ok_or(VaultError::NotInAntechamber)?;
+    let savestate = vm.get_state();
+
+    let mut tiles = [[Tile::Operator('+'); SIZE]; SIZE];
+    let mut target = None;
+    for (row, tiles) in tiles.iter_mut().enumerate() {
+        for (column, tile) in tiles.iter_mut().enumerate() {
+            let unreadable = VaultError::UnreadableRoom(row, column);
+            if (row, column) == START {
+                *tile = quoted(&antechamber.description)
+                    .and_then(Tile::parse)
+                    .ok_or(unreadable)?;
+                continue;
+            }
+
+            vm.apply_state(savestate.clone());
+            let walk = "east\n".repeat(column) + &"north\n".repeat(START.0 - row);
+            vm.prefeed_stdin(&walk);
+            let room = GameState::from_output(&vm.run_until_input().output).room;
+            *tile = room.as_ref().and_then(mosaic).ok_or(unreadable)?;
+            if (row, column) == DOOR {
+                // the door shows its weight before the mosaic
+                target = room
+                    .as_ref()
+                    .and_then(|room| quoted(&room.description))
+                    .and_then(|word| word.parse::<i64>().ok());
+            }
+        }
+    }
+    vm.apply_state(savestate);
+
+    let target = target.ok_or(VaultError::UnreadableRoom(DOOR.0, DOOR.1))?;
+    Ok(Grid { tiles, target })
+}
+
+/// Reads and solves the grid from the antechamber and returns the walk as replay lines,
+/// starting with `take orb` unless the orb was taken already and ending with an expectation of
+/// the unlocked door. Nothing is sent to the machine.
+pub fn replay_snippet(vm: &mut VirtualMachine) -> Result<String, VaultError> {
+    let grid = read_grid(vm)?;
+    let directions = grid.solve().ok_or(VaultError::NoPath)?;
+    let orb_in_room = look(vm).is_some_and(|room| room.things.iter().any(|thing| thing == "orb"));
+
+    let mut snippet = format!(
+        "# vault: {} to {} in {} steps\n",
+        grid.start_value(),
+        grid.target,
+        directions.len()
+    );
+    if orb_in_room {
+        snippet.push_str("take orb\n");
+    }
+    for direction in directions {
+        snippet.push_str(direction);
+        snippet.push('\n');
+    }
+    snippet.push_str(&format!("#expect \"{}\"\n", DOOR_UNLOCKED));
+    Ok(snippet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Grid, Tile};
+
+    #[test]
+    fn test_solve_grid() {
+        use Tile::{Number as N, Operator as O};
+        let grid = Grid {
+            tiles: [
+                [O('*'), N(8), O('-'), N(1)],
+                [N(4), O('*'), N(11), O('*')],
+                [O('+'), N(4), O('-'), N(18)],
+                [N(22), O('-'), N(9), O('*')],
+            ],
+            target: 30,
+        };
+        let directions = grid.solve().unwrap();
+        assert_eq!(directions.len(), 12);
+        assert_eq!(
+            directions,
+            vec![
+                "north", "east", "east", "north", "west", "south", "east", "east", "west", "north",
+                "north", "east"
+            ]
+        );
+    }
+}
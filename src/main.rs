@@ -1,40 +1,446 @@
-use std::{env, fs, thread};
+use std::{
+    fmt::Display,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process, thread,
+};
 
-use vm::{subscription::VirtualMachineSubscription, VirtualMachine};
+use clap::Parser;
+use cli::{Cli, Command, Puzzle, SessionArgs};
+use game::{explorer, teleporter, vault};
+use tools::{analysis::Analysis, assembler, disassembler::Disassembly};
+use vm::{
+    builder::VirtualMachineBuilder,
+    codes::CodeDetector,
+    devices::{StdinInput, StdoutOutput},
+    replay::{Replay, ReplayRunner, ReplayStatus},
+    subscription::VirtualMachineSubscription,
+    trace::TraceReader,
+    transcript::Transcript,
+    VirtualMachine,
+};
 
+pub mod cli;
+pub mod game;
+pub mod tools;
 pub mod viewer;
 pub mod vm;
 
-fn transform_bytes_to_program_code(content: &[u8]) -> Vec<u16> {
-    let mut program_code = vec![];
-    for i in (0..content.len()).step_by(2) {
-        let value = u16::from_le_bytes(content[i..(i + 2)].try_into().unwrap());
-        program_code.push(value);
+#[derive(Debug)]
+pub enum ProgramError {
+    Io(io::Error),
+    /// Programs consist of 16 bit words, holds the length in bytes.
+    OddLength(usize),
+}
+
+impl Display for ProgramError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::OddLength(length) => {
+                write!(f, "odd length of {} bytes, expected 16 bit words", length)
+            }
+        }
+    }
+}
+
+fn transform_bytes_to_program_code(content: &[u8]) -> Result<Vec<u16>, ProgramError> {
+    if !content.len().is_multiple_of(2) {
+        return Err(ProgramError::OddLength(content.len()));
     }
+    Ok(content
+        .chunks_exact(2)
+        .map(|word| u16::from_le_bytes([word[0], word[1]]))
+        .collect())
+}
+
+fn transform_program_code_to_bytes(program_code: &[u16]) -> Vec<u8> {
     program_code
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let file_path = args.get(1).expect("Expecting a file path as argument");
-    let content = fs::read(file_path).expect("Could not read file");
-    let program = transform_bytes_to_program_code(&content);
+fn read_program(file_path: &Path) -> Result<Vec<u16>, ProgramError> {
+    let content = fs::read(file_path).map_err(ProgramError::Io)?;
+    transform_bytes_to_program_code(&content)
+}
 
-    let (subscriber, subscription) = VirtualMachineSubscription::setup();
+/// Prints the error and exits.
+fn fail(message: impl Display) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
 
-    let _handle = thread::spawn(move || {
-        let mut vm = VirtualMachine::new(subscriber);
-        vm.load_data(&program);
+fn load_program(file_path: &Path) -> Vec<u16> {
+    read_program(file_path).unwrap_or_else(|error| {
+        fail(format!(
+            "Could not load program '{}': {}",
+            file_path.display(),
+            error
+        ))
+    })
+}
+
+fn read_text(file_path: &Path) -> String {
+    fs::read_to_string(file_path).unwrap_or_else(|error| {
+        fail(format!(
+            "Could not read '{}': {}",
+            file_path.display(),
+            error
+        ))
+    })
+}
+
+fn write_file(file_path: &Path, contents: impl AsRef<[u8]>) {
+    fs::write(file_path, contents).unwrap_or_else(|error| {
+        fail(format!(
+            "Could not write '{}': {}",
+            file_path.display(),
+            error
+        ))
+    })
+}
+
+fn read_code_hashes(file_path: &Path) -> Vec<String> {
+    let hashes = CodeDetector::parse_hashes(&read_text(file_path));
+    if hashes.is_empty() {
+        fail(format!("No code hashes in '{}'", file_path.display()));
+    }
+    hashes
+}
+
+fn parse_replay(content: &str) -> Replay {
+    Replay::parse(content)
+        .unwrap_or_else(|error| fail(format!("Could not parse replay: {}", error)))
+}
+
+/// Builder for a program with the savestate slots in the directory.
+fn program_builder(binary: &Path, savestate_dir: &Path) -> VirtualMachineBuilder {
+    VirtualMachine::builder()
+        .program(&load_program(binary))
+        .savestate_directory(savestate_dir)
+}
 
-        if let Ok(content) = fs::read_to_string(vm::HISTORY_FILE_PATH) {
-            for c in content.chars() {
-                vm.stdin_buffer.push_back(c as u8);
+/// Builder for an interactive session, with history path and transcript.
+fn session_builder(session: &SessionArgs, savestate_dir: &Path) -> VirtualMachineBuilder {
+    let mut builder =
+        program_builder(&session.binary, savestate_dir).history_path(&session.history);
+    if let Some(path) = &session.code_hashes {
+        builder = builder.code_hashes(read_code_hashes(path));
+    }
+    if !session.no_transcript {
+        match Transcript::create(&session.transcript_dir) {
+            Ok(transcript) => builder = builder.transcript(transcript),
+            Err(error) => eprintln!("Could not create transcript: {}", error),
+        }
+    }
+    builder
+}
+
+/// Restores a savestate slot or attaches the replay, then applies the initial overrides.
+fn setup_vm(vm: &mut VirtualMachine, session: SessionArgs) {
+    vm.load_slots_from_disk();
+
+    if let Some(name) = &session.load_state {
+        if let Err(error) = vm.load_state_from_slot(name) {
+            fail(format!("Could not load slot '{}': {}", name, error));
+        }
+    } else if !session.no_replay {
+        // without an explicit replay the previous session is continued from the history file,
+        // which holds typed input only, so `#` lines in it are not directives
+        let replay = match &session.replay {
+            Some(path) => Some(parse_replay(&read_text(path))),
+            None => fs::read_to_string(&session.history)
+                .ok()
+                .map(|content| Replay::from_input(&content)),
+        };
+        if let Some(replay) = replay {
+            vm.replay = Some(ReplayRunner::new(replay).with_solver(game::solve_puzzle));
+        }
+    }
+
+    for (register, value) in session.registers {
+        vm.memory.registers[register] = value;
+    }
+    for command in session.breakpoints {
+        vm.handle_breakpoint_command(command);
+    }
+
+    if let Some(path) = session.trace {
+        if let Err(error) = vm.start_trace(&path) {
+            fail(format!(
+                "Could not start trace '{}': {}",
+                path.display(),
+                error
+            ));
+        }
+    }
+}
+
+fn save_state(vm: &mut VirtualMachine, name: Option<String>) {
+    if let Some(name) = name {
+        if let Err(error) = vm.save_state_to_slot(&name) {
+            eprintln!("Could not save slot '{}': {}", name, error);
+        }
+    }
+}
+
+fn run(session: SessionArgs, save_state_slot: Option<String>, savestate_dir: &Path) {
+    let mut vm = session_builder(&session, savestate_dir)
+        .input(StdinInput)
+        .output(StdoutOutput)
+        .build();
+    setup_vm(&mut vm, session);
+
+    if let Some(mut runner) = vm.replay.take() {
+        match runner.run(&mut vm) {
+            Ok(ReplayStatus::Finished) => {}
+            Ok(ReplayStatus::Break(line)) => eprintln!("Replay stopped at line {}", line),
+            Ok(ReplayStatus::Paused) => eprintln!("Replay stopped at a breakpoint"),
+            Err(error) => {
+                vm.output.flush();
+                fail(format!("Replay {}", error));
             }
-            vm.stdin_history = content;
         }
+    }
+    if let Err(error) = vm.run_headless() {
+        eprintln!("Fault at {}: {}", vm.program_counter, error);
+    }
+    if let Err(error) = vm.stop_trace() {
+        eprintln!("Could not finish trace: {}", error);
+    }
+    save_state(&mut vm, save_state_slot);
+    process::exit(if vm.halted { 0 } else { 1 });
+}
+
+fn tui(session: SessionArgs, savestate_dir: &Path) {
+    let builder = session_builder(&session, savestate_dir);
+    let (subscriber, subscription) = VirtualMachineSubscription::setup();
+
+    let _handle = thread::spawn(move || {
+        let mut vm = builder.subscriber(subscriber).rewind(true).build();
+        setup_vm(&mut vm, session);
 
         vm.run();
     });
 
     let _ = viewer::main(subscription);
 }
+
+/// Runs a replay on its own and reports where it diverged.
+fn replay(
+    binary: &Path,
+    replay_path: &Path,
+    save_state_slot: Option<String>,
+    verbose: bool,
+    savestate_dir: &Path,
+) {
+    let mut builder = program_builder(binary, savestate_dir);
+    if verbose {
+        builder = builder.output(StdoutOutput);
+    }
+    let mut vm = builder.build();
+    let mut runner =
+        ReplayRunner::new(parse_replay(&read_text(replay_path))).with_solver(game::solve_puzzle);
+
+    loop {
+        match runner.run(&mut vm) {
+            Ok(ReplayStatus::Finished) => break,
+            Ok(ReplayStatus::Break(line)) => {
+                eprintln!("Passed #break at line {}", line);
+                vm.resume();
+            }
+            Ok(ReplayStatus::Paused) => {
+                eprintln!("Passed a breakpoint at {}", vm.program_counter);
+                vm.resume();
+            }
+            Err(error) => {
+                vm.output.flush();
+                fail(format!("Replay {}", error));
+            }
+        }
+    }
+    vm.output.flush();
+    eprintln!("Replay finished after {} cycles", vm.cycle);
+    save_state(&mut vm, save_state_slot);
+}
+
+/// Runs a replay to its end without output, failing on a divergence.
+fn run_replay(vm: &mut VirtualMachine, replay_path: &Path) {
+    let mut runner =
+        ReplayRunner::new(parse_replay(&read_text(replay_path))).with_solver(game::solve_puzzle);
+    loop {
+        match runner.run(vm) {
+            Ok(ReplayStatus::Finished) => break,
+            Ok(_) => vm.resume(),
+            Err(error) => fail(format!("Replay {}", error)),
+        }
+    }
+}
+
+/// Prints the README table for the codes the replay and the extra texts contain.
+fn codes(
+    binary: &Path,
+    replay_path: &Path,
+    code_hashes: Option<PathBuf>,
+    scan: Vec<PathBuf>,
+    savestate_dir: &Path,
+) {
+    let mut builder = program_builder(binary, savestate_dir);
+    if let Some(path) = code_hashes {
+        builder = builder.code_hashes(read_code_hashes(&path));
+    }
+    let mut vm = builder.build();
+    run_replay(&mut vm, replay_path);
+    for path in scan {
+        vm.codes.scan(&read_text(&path));
+    }
+
+    for (idx, code) in vm.codes.codes.iter().enumerate() {
+        if let Some(text) = code.label() {
+            eprintln!("#{} {}", idx + 1, text);
+        }
+    }
+    print!("{}", vm.codes.status_table());
+}
+
+fn solve(puzzle: Puzzle, savestate_dir: &Path) {
+    match puzzle {
+        Puzzle::Teleporter {
+            threads,
+            binary,
+            replay,
+            save_state: save_state_slot,
+        } => {
+            let values = teleporter::solve(threads);
+            let Some(r7) = values.first().copied() else {
+                fail("No r7 passes the teleporter confirmation");
+            };
+            println!("r7 = {}", r7);
+
+            if let (Some(binary), Some(replay)) = (binary, replay) {
+                let mut vm = program_builder(&binary, savestate_dir).build();
+                run_replay(&mut vm, &replay);
+                if teleporter::patch(&mut vm, r7).is_none() {
+                    fail("Teleporter confirmation call not found");
+                }
+                vm.prefeed_stdin("use teleporter\n");
+                print!("{}", vm.run_until_input().output);
+                save_state(&mut vm, save_state_slot);
+            }
+        }
+        Puzzle::Vault {
+            binary,
+            load_state,
+            replay,
+        } => {
+            let mut vm = program_builder(&binary, savestate_dir).build();
+            if let Some(name) = load_state {
+                vm.load_slots_from_disk();
+                if let Err(error) = vm.load_state_from_slot(&name) {
+                    fail(format!("Could not load slot '{}': {}", name, error));
+                }
+                // the slot may have been saved while paused in the viewer
+                vm.resume();
+            }
+            if let Some(path) = replay {
+                run_replay(&mut vm, &path);
+            }
+            match vault::replay_snippet(&mut vm) {
+                Ok(snippet) => print!("{}", snippet),
+                Err(error) => fail(format!("Could not solve the vault: {}", error)),
+            }
+        }
+        Puzzle::Map {
+            binary,
+            json_output,
+            dot_output,
+            replay,
+            max_rooms,
+        } => {
+            let mut vm = program_builder(&binary, savestate_dir).build();
+            if let Some(path) = replay {
+                run_replay(&mut vm, &path);
+            }
+            let map = explorer::explore(&mut vm, max_rooms);
+            eprintln!(
+                "Found {} rooms and {} passages",
+                map.rooms.len(),
+                map.passages.len()
+            );
+
+            let json = serde_json::to_string_pretty(&map.to_json())
+                .unwrap_or_else(|error| fail(format!("Could not encode map: {}", error)));
+            write_file(&json_output, json);
+            if let Some(dot_path) = dot_output {
+                write_file(&dot_path, map.to_dot());
+            }
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let savestate_dir = cli.savestate_dir.as_path();
+    match cli.command {
+        Command::Run {
+            session,
+            save_state,
+        } => run(session, save_state, savestate_dir),
+        Command::Tui { session } => tui(session, savestate_dir),
+        Command::Disasm { binary } => {
+            let program = load_program(&binary);
+            print!("{}", Disassembly::new(&program).render());
+        }
+        Command::Analyze { binary, dot } => {
+            let program = load_program(&binary);
+            let analysis = Analysis::new(&Disassembly::new(&program));
+            if dot {
+                print!("{}", analysis.render_dot());
+            } else {
+                print!("{}", analysis.render_listing());
+            }
+        }
+        Command::Asm { source, output } => {
+            let text = read_text(&source);
+            match assembler::assemble(&text) {
+                Ok(program) => write_file(&output, transform_program_code_to_bytes(&program)),
+                Err(error) => fail(format!("{}: {}", source.display(), error)),
+            }
+        }
+        Command::Replay {
+            binary,
+            replay: replay_path,
+            save_state,
+            verbose,
+        } => replay(&binary, &replay_path, save_state, verbose, savestate_dir),
+        Command::Trace { trace, from, to } => {
+            let range = from.unwrap_or(0)..=to.unwrap_or(u16::MAX);
+
+            let mut stdout = io::stdout().lock();
+            let trace_error =
+                |error| format!("Could not read trace '{}': {}", trace.display(), error);
+            let mut reader =
+                TraceReader::open(&trace).unwrap_or_else(|error| fail(trace_error(error)));
+            while let Some((cycle, record)) = reader
+                .next_record()
+                .unwrap_or_else(|error| fail(trace_error(error)))
+            {
+                if range.contains(&record.program_counter)
+                    && writeln!(stdout, "{}", record.render(cycle)).is_err()
+                {
+                    break;
+                }
+            }
+        }
+        Command::Codes {
+            binary,
+            replay,
+            code_hashes,
+            scan,
+        } => codes(&binary, &replay, code_hashes, scan, savestate_dir),
+        Command::Solve { puzzle } => solve(puzzle, savestate_dir),
+    }
+}
@@ -0,0 +1,337 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt::Write,
+};
+
+use crate::{
+    tools::disassembler::{branch_targets, ends_flow, Disassembly, Entry, Origin},
+    vm::{memory::MAX_ADDRESS, opcodes::Instruction},
+};
+
+/// A straight line of instructions with a single entry and exit.
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    pub start: u16,
+    /// Address after the last instruction of the block.
+    pub end: u16,
+    pub instructions: Vec<(u16, Instruction)>,
+    pub successors: Vec<u16>,
+    pub calls: Vec<u16>,
+    pub indirect_calls: usize,
+    pub reachable: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub entry: u16,
+    pub blocks: BTreeSet<u16>,
+    pub calls: BTreeSet<u16>,
+    pub callers: BTreeSet<u16>,
+    pub indirect_calls: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub blocks: BTreeMap<u16, BasicBlock>,
+    pub functions: BTreeMap<u16, Function>,
+}
+
+impl Analysis {
+    pub fn new(disassembly: &Disassembly) -> Self {
+        let mut analysis = Self::default();
+        analysis.split_blocks(disassembly);
+        analysis.collect_functions(disassembly);
+        analysis
+    }
+
+    fn split_blocks(&mut self, disassembly: &Disassembly) {
+        let mut leaders: BTreeSet<u16> = BTreeSet::from([0]);
+        leaders.extend(disassembly.jump_targets.iter());
+        leaders.extend(disassembly.call_targets.iter());
+
+        let mut current: Option<BasicBlock> = None;
+        for (&address, entry) in disassembly.entries.iter() {
+            let (instruction, origin) = match entry {
+                Entry::Code(instruction, origin) => (*instruction, *origin),
+                Entry::Data(_, _) => {
+                    if let Some(block) = current.take() {
+                        self.blocks.insert(block.start, block);
+                    }
+                    continue;
+                }
+            };
+
+            if leaders.contains(&address) {
+                if let Some(mut block) = current.take() {
+                    block.successors.push(address);
+                    self.blocks.insert(block.start, block);
+                }
+            }
+
+            let block = current.get_or_insert_with(|| BasicBlock {
+                start: address,
+                end: address,
+                instructions: vec![],
+                successors: vec![],
+                calls: vec![],
+                indirect_calls: 0,
+                reachable: origin == Origin::Reachable,
+            });
+            block.instructions.push((address, instruction));
+            block.end = address + instruction.byte_length() as u16;
+
+            match instruction {
+                Instruction::Call(target) if target <= MAX_ADDRESS => block.calls.push(target),
+                Instruction::Call(_) => block.indirect_calls += 1,
+                _ => {}
+            }
+
+            let is_branch = matches!(
+                instruction,
+                Instruction::Jump(_)
+                    | Instruction::JumpIfNonZero(_, _)
+                    | Instruction::JumpIfZero(_, _)
+            );
+            if is_branch || ends_flow(&instruction) {
+                let mut block = current.take().unwrap();
+                if is_branch {
+                    block.successors.extend(
+                        branch_targets(&instruction)
+                            .into_iter()
+                            .filter(|target| disassembly.entries.contains_key(target)),
+                    );
+                }
+                if !ends_flow(&instruction) && disassembly.entries.contains_key(&block.end) {
+                    block.successors.push(block.end);
+                }
+                self.blocks.insert(block.start, block);
+            }
+        }
+
+        if let Some(block) = current.take() {
+            self.blocks.insert(block.start, block);
+        }
+    }
+
+    fn collect_functions(&mut self, disassembly: &Disassembly) {
+        let mut entries: BTreeSet<u16> = disassembly.call_targets.clone();
+        entries.insert(0);
+
+        for &entry in entries.iter() {
+            if !self.blocks.contains_key(&entry) {
+                continue;
+            }
+
+            let mut function = Function {
+                entry,
+                blocks: BTreeSet::new(),
+                calls: BTreeSet::new(),
+                callers: BTreeSet::new(),
+                indirect_calls: 0,
+            };
+            let mut queue = VecDeque::from([entry]);
+            while let Some(start) = queue.pop_front() {
+                let Some(block) = self.blocks.get(&start) else {
+                    continue;
+                };
+                if !function.blocks.insert(start) {
+                    continue;
+                }
+                function.calls.extend(block.calls.iter());
+                function.indirect_calls += block.indirect_calls;
+                for successor in block.successors.iter() {
+                    // jumping into another function entry is treated as a tail call
+                    if entries.contains(successor) && *successor != entry {
+                        function.calls.insert(*successor);
+                    } else {
+                        queue.push_back(*successor);
+                    }
+                }
+            }
+            self.functions.insert(entry, function);
+        }
+
+        let edges: Vec<(u16, u16)> = self
+            .functions
+            .values()
+            .flat_map(|function| function.calls.iter().map(|call| (function.entry, *call)))
+            .collect();
+        for (caller, callee) in edges {
+            if let Some(function) = self.functions.get_mut(&callee) {
+                function.callers.insert(caller);
+            }
+        }
+    }
+
+    fn function_reachable(&self, function: &Function) -> bool {
+        self.blocks
+            .get(&function.entry)
+            .is_some_and(|block| block.reachable)
+    }
+
+    /// Exports the call graph in Graphviz DOT format.
+    pub fn render_dot(&self) -> String {
+        let mut text =
+            String::from("digraph callgraph {\n    node [shape=box, fontname=monospace];\n");
+        for function in self.functions.values() {
+            let style = if self.function_reachable(function) {
+                ""
+            } else {
+                ", style=dashed"
+            };
+            writeln!(
+                text,
+                "    fn_{} [label=\"fn_{}\\n{} blocks\"{}];",
+                function.entry,
+                function.entry,
+                function.blocks.len(),
+                style
+            )
+            .unwrap();
+        }
+        for function in self.functions.values() {
+            for call in function.calls.iter() {
+                writeln!(text, "    fn_{} -> fn_{};", function.entry, call).unwrap();
+            }
+        }
+        text.push_str("}\n");
+        text
+    }
+
+    /// Textual listing of all functions with their blocks, callees and callers.
+    pub fn render_listing(&self) -> String {
+        let mut text = String::new();
+        for function in self.functions.values() {
+            let instructions: usize = function
+                .blocks
+                .iter()
+                .filter_map(|start| self.blocks.get(start))
+                .map(|block| block.instructions.len())
+                .sum();
+            writeln!(
+                text,
+                "fn_{}{}: {} blocks, {} instructions",
+                function.entry,
+                if self.function_reachable(function) {
+                    ""
+                } else {
+                    " (unreached)"
+                },
+                function.blocks.len(),
+                instructions
+            )
+            .unwrap();
+
+            let join = |set: &BTreeSet<u16>| {
+                set.iter()
+                    .map(|address| format!("fn_{}", address))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            if !function.calls.is_empty() || function.indirect_calls > 0 {
+                write!(text, "  calls: {}", join(&function.calls)).unwrap();
+                if function.indirect_calls > 0 {
+                    write!(text, " (+{} indirect)", function.indirect_calls).unwrap();
+                }
+                writeln!(text).unwrap();
+            }
+            if !function.callers.is_empty() {
+                writeln!(text, "  called by: {}", join(&function.callers)).unwrap();
+            }
+            for start in function.blocks.iter() {
+                let block = &self.blocks[start];
+                let successors: Vec<String> =
+                    block.successors.iter().map(|s| s.to_string()).collect();
+                writeln!(
+                    text,
+                    "  block {}..{} -> [{}]",
+                    block.start,
+                    block.end,
+                    successors.join(", ")
+                )
+                .unwrap();
+            }
+            writeln!(text).unwrap();
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::{
+        tools::{assembler::assemble, disassembler::Disassembly},
+        transform_bytes_to_program_code,
+    };
+
+    use super::Analysis;
+
+    #[test]
+    fn test_blocks_and_functions() {
+        let source = "
+main:   CALL func
+        CALL tail
+        HALT
+func:   JT r0 skip
+        OUT 'a'
+skip:   JMP tail
+tail:   OUT 'b'
+        RET
+";
+        let program = assemble(source).unwrap();
+        let analysis = Analysis::new(&Disassembly::new(&program));
+
+        let blocks: Vec<(u16, u16, Vec<u16>)> = analysis
+            .blocks
+            .values()
+            .map(|block| (block.start, block.end, block.successors.clone()))
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                (0, 5, vec![]),
+                (5, 8, vec![10, 8]),
+                (8, 10, vec![10]),
+                (10, 12, vec![12]),
+                (12, 15, vec![]),
+            ]
+        );
+        assert_eq!(analysis.blocks[&0].calls, vec![5, 12]);
+
+        let main = &analysis.functions[&0];
+        assert_eq!(main.calls, BTreeSet::from([5, 12]));
+        let func = &analysis.functions[&5];
+        assert_eq!(func.blocks, BTreeSet::from([5, 8, 10]));
+        // the jump into `tail` is a tail call, not a block of `func`
+        assert_eq!(func.calls, BTreeSet::from([12]));
+        assert_eq!(func.callers, BTreeSet::from([0]));
+        let tail = &analysis.functions[&12];
+        assert_eq!(tail.blocks, BTreeSet::from([12]));
+        assert_eq!(tail.callers, BTreeSet::from([0, 5]));
+    }
+
+    #[test]
+    fn test_render_challenge() {
+        let program =
+            transform_bytes_to_program_code(include_bytes!("../../payloads/challenge.bin"))
+                .unwrap();
+        let analysis = Analysis::new(&Disassembly::new(&program));
+        assert!(analysis.functions.len() > 1);
+
+        let dot = analysis.render_dot();
+        assert!(dot.starts_with("digraph callgraph {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("    fn_0 [label=\"fn_0\\n"));
+        assert!(dot.contains("    fn_0 -> fn_"));
+
+        let listing = analysis.render_listing();
+        assert!(listing.starts_with("fn_0: "));
+        assert_eq!(
+            listing.matches(" blocks, ").count(),
+            analysis.functions.len()
+        );
+        assert!(listing.contains("  called by: fn_0"));
+    }
+}
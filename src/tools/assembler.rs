@@ -0,0 +1,369 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::vm::{
+    memory::{MAX_ADDRESS, REGISTER_ADDRESS_START},
+    opcodes::Instruction,
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AssemblerErrorKind {
+    UnknownMemnonic(String),
+    UnknownDirective(String),
+    WrongOperandCount(String, usize),
+    InvalidOperand(String),
+    UnknownLabel(String),
+    DuplicateLabel(String),
+    /// A label defined past the end of memory, with its address.
+    LabelOutOfRange(String, usize),
+    AddressBehind(u16),
+    UnterminatedString,
+    ProgramTooLarge(usize),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AssemblerError {
+    pub line: usize,
+    pub kind: AssemblerErrorKind,
+}
+
+impl Display for AssemblerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            AssemblerErrorKind::UnknownMemnonic(name) => write!(f, "unknown memnonic '{}'", name),
+            AssemblerErrorKind::UnknownDirective(name) => {
+                write!(f, "unknown directive '{}'", name)
+            }
+            AssemblerErrorKind::WrongOperandCount(name, expected) => {
+                write!(f, "{} expects {} operand(s)", name, expected)
+            }
+            AssemblerErrorKind::InvalidOperand(operand) => {
+                write!(f, "invalid operand '{}'", operand)
+            }
+            AssemblerErrorKind::UnknownLabel(label) => write!(f, "unknown label '{}'", label),
+            AssemblerErrorKind::DuplicateLabel(label) => {
+                write!(f, "label '{}' defined twice", label)
+            }
+            AssemblerErrorKind::LabelOutOfRange(label, address) => {
+                write!(f, "label '{}' at {} is outside of memory", label, address)
+            }
+            AssemblerErrorKind::AddressBehind(address) => {
+                write!(f, "address {} is behind the current position", address)
+            }
+            AssemblerErrorKind::UnterminatedString => write!(f, "unterminated string"),
+            AssemblerErrorKind::ProgramTooLarge(length) => {
+                write!(f, "program of {} words does not fit into memory", length)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Operand {
+    Value(u16),
+    Label(String),
+}
+
+#[derive(Debug)]
+struct Statement {
+    line: usize,
+    words: Vec<Operand>,
+}
+
+/// Looks up the opcode of a memnonic as printed by [`Instruction::memnonic`].
+pub fn opcode_for_memnonic(memnonic: &str) -> Option<u16> {
+    (0..=21).find(|&opcode| {
+        Instruction::try_from(&[opcode, 0, 0, 0][..])
+            .is_ok_and(|instruction| instruction.memnonic().eq_ignore_ascii_case(memnonic))
+    })
+}
+
+fn operand_count(opcode: u16) -> usize {
+    Instruction::try_from(&[opcode, 0, 0, 0][..])
+        .map(|instruction| instruction.byte_length() - 1)
+        .unwrap_or_default()
+}
+
+fn parse_operand(token: &str, line: usize) -> Result<Operand, AssemblerError> {
+    let invalid = || AssemblerError {
+        line,
+        kind: AssemblerErrorKind::InvalidOperand(token.to_string()),
+    };
+
+    if let Some(register) = token
+        .strip_prefix('r')
+        .and_then(|idx| idx.parse::<u16>().ok())
+    {
+        return if register < 8 {
+            Ok(Operand::Value(REGISTER_ADDRESS_START + register))
+        } else {
+            Err(invalid())
+        };
+    }
+
+    if token.len() >= 3 && token.starts_with('\'') && token.ends_with('\'') {
+        let inner = &token[1..token.len() - 1];
+        return match inner {
+            "\\n" => Ok(Operand::Value(b'\n' as u16)),
+            "\\'" => Ok(Operand::Value(b'\'' as u16)),
+            "\\\\" => Ok(Operand::Value(b'\\' as u16)),
+            _ if inner.chars().count() == 1 => {
+                Ok(Operand::Value(inner.chars().next().unwrap() as u16))
+            }
+            _ => Err(invalid()),
+        };
+    }
+
+    let number = if let Some(hex) = token.strip_prefix("0x") {
+        u16::from_str_radix(hex, 16).ok()
+    } else if token.starts_with(|c: char| c.is_ascii_digit()) {
+        Some(token.parse::<u16>().map_err(|_| invalid())?)
+    } else {
+        None
+    };
+
+    match number {
+        Some(value) => Ok(Operand::Value(value)),
+        None if token.chars().all(|c| c.is_alphanumeric() || c == '_') => {
+            Ok(Operand::Label(token.to_string()))
+        }
+        None => Err(invalid()),
+    }
+}
+
+/// Splits a line into tokens, keeping quoted strings and character literals intact
+/// and dropping everything after a `;`.
+fn tokenize(text: &str, line: usize) -> Result<Vec<String>, AssemblerError> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            ';' => break,
+            c if c.is_whitespace() || c == ',' => {
+                chars.next();
+            }
+            '"' | '\'' => {
+                let mut token = String::from(c);
+                chars.next();
+                let mut terminated = false;
+                while let Some(next) = chars.next() {
+                    token.push(next);
+                    if next == '\\' {
+                        if let Some(escaped) = chars.next() {
+                            token.push(escaped);
+                        }
+                    } else if next == c {
+                        terminated = true;
+                        break;
+                    }
+                }
+                if !terminated {
+                    return Err(AssemblerError {
+                        line,
+                        kind: AssemblerErrorKind::UnterminatedString,
+                    });
+                }
+                tokens.push(token);
+            }
+            _ => {
+                let mut token = String::new();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || next == ',' || next == ';' {
+                        break;
+                    }
+                    token.push(next);
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn unescape_string(token: &str) -> Vec<u16> {
+    let mut result = vec![];
+    let mut chars = token[1..token.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        let c = match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('n') => '\n',
+                Some(other) => other,
+                None => break,
+            },
+            (c, false) => c,
+        };
+        result.push(c as u16);
+    }
+    result
+}
+
+/// Assembles source text into program words.
+///
+/// Supported syntax per line, with `;` starting a comment:
+/// - `label:` defines a label at the current address
+/// - `1234:` moves the current address forward (padding with zeros)
+/// - `MEMNONIC op1 op2 ...` with operands as numbers, `r0..r7`, `'c'` or labels
+/// - `.data op1 op2 ...` emits raw words
+/// - `.string "text"` emits one word per character
+pub fn assemble(source: &str) -> Result<Vec<u16>, AssemblerError> {
+    let mut statements: Vec<Statement> = vec![];
+    let mut labels: HashMap<String, u16> = HashMap::new();
+    let mut address: usize = 0;
+
+    for (idx, text) in source.lines().enumerate() {
+        let line = idx + 1;
+        let error = |kind| AssemblerError { line, kind };
+        let mut tokens = tokenize(text, line)?;
+
+        while let Some(marker) = tokens.first().and_then(|t| t.strip_suffix(':')) {
+            if let Ok(target) = marker.parse::<u16>() {
+                if (target as usize) < address {
+                    return Err(error(AssemblerErrorKind::AddressBehind(target)));
+                }
+                if target as usize > address {
+                    statements.push(Statement {
+                        line,
+                        words: (address..target as usize)
+                            .map(|_| Operand::Value(0))
+                            .collect(),
+                    });
+                    address = target as usize;
+                }
+            } else if address > MAX_ADDRESS as usize {
+                return Err(error(AssemblerErrorKind::LabelOutOfRange(
+                    marker.to_string(),
+                    address,
+                )));
+            } else if labels.insert(marker.to_string(), address as u16).is_some() {
+                return Err(error(AssemblerErrorKind::DuplicateLabel(
+                    marker.to_string(),
+                )));
+            }
+            tokens.remove(0);
+        }
+
+        let Some(head) = tokens.first() else {
+            continue;
+        };
+
+        let words = match head.as_str() {
+            ".data" => tokens[1..]
+                .iter()
+                .map(|token| parse_operand(token, line))
+                .collect::<Result<Vec<_>, _>>()?,
+            ".string" => {
+                let mut words = vec![];
+                for token in tokens[1..].iter() {
+                    if !token.starts_with('"') {
+                        return Err(error(AssemblerErrorKind::InvalidOperand(token.clone())));
+                    }
+                    words.extend(unescape_string(token).into_iter().map(Operand::Value));
+                }
+                words
+            }
+            directive if directive.starts_with('.') => {
+                return Err(error(AssemblerErrorKind::UnknownDirective(
+                    directive.to_string(),
+                )));
+            }
+            memnonic => {
+                let opcode = opcode_for_memnonic(memnonic).ok_or_else(|| {
+                    error(AssemblerErrorKind::UnknownMemnonic(memnonic.to_string()))
+                })?;
+                let expected = operand_count(opcode);
+                if tokens.len() - 1 != expected {
+                    return Err(error(AssemblerErrorKind::WrongOperandCount(
+                        memnonic.to_uppercase(),
+                        expected,
+                    )));
+                }
+                let mut words = vec![Operand::Value(opcode)];
+                for token in tokens[1..].iter() {
+                    words.push(parse_operand(token, line)?);
+                }
+                words
+            }
+        };
+
+        address += words.len();
+        statements.push(Statement { line, words });
+    }
+
+    let mut program = Vec::with_capacity(address);
+    for statement in statements {
+        for word in statement.words {
+            program.push(match word {
+                Operand::Value(value) => value,
+                Operand::Label(label) => match labels.get(&label) {
+                    Some(&address) => address,
+                    None => {
+                        return Err(AssemblerError {
+                            line: statement.line,
+                            kind: AssemblerErrorKind::UnknownLabel(label),
+                        })
+                    }
+                },
+            });
+        }
+    }
+
+    if program.len() > MAX_ADDRESS as usize + 1 {
+        return Err(AssemblerError {
+            line: source.lines().count(),
+            kind: AssemblerErrorKind::ProgramTooLarge(program.len()),
+        });
+    }
+
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tools::disassembler::Disassembly, transform_bytes_to_program_code};
+
+    use super::{assemble, AssemblerErrorKind};
+
+    #[test]
+    fn test_assemble_spec_example() {
+        let program = assemble("ADD r0 r1 4\nOUT r0\n").unwrap();
+        assert_eq!(program, vec![9, 32768, 32769, 4, 19, 32768]);
+    }
+
+    #[test]
+    fn test_assemble_labels_and_directives() {
+        let source = "
+start:  SET r0 'A'      ; comment
+        JMP end
+text:   .string \"hi\\n\"
+        .data 7 start
+end:    HALT
+";
+        let program = assemble(source).unwrap();
+        assert_eq!(program, vec![1, 32768, 65, 6, 10, 104, 105, 10, 7, 0, 0]);
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        assert!(assemble("FOO r0").is_err());
+        assert!(assemble("SET r0").is_err());
+        assert!(assemble("SET r9 1").is_err());
+        assert!(assemble("JMP nowhere").is_err());
+        assert!(assemble("10: NOOP\n5: NOOP").is_err());
+        assert_eq!(
+            assemble("32767: NOOP\nend: JMP end").unwrap_err().kind,
+            AssemblerErrorKind::LabelOutOfRange("end".to_string(), 32768)
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_disassembly() {
+        let program =
+            transform_bytes_to_program_code(include_bytes!("../../payloads/challenge.bin"))
+                .unwrap();
+        let listing = Disassembly::new(&program).render();
+        assert_eq!(assemble(&listing).unwrap(), program);
+    }
+}
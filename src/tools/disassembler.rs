@@ -0,0 +1,297 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt::Write,
+};
+
+use crate::vm::opcodes::{DecoderError, Instruction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    /// Reached by following control flow from the entry point.
+    Reachable,
+    /// Only found by the linear sweep over the remaining image.
+    Linear,
+}
+
+/// Why a word is listed as data instead of an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataReason {
+    Undecodable(DecoderError),
+    /// Decodes, but the instruction would run into already disassembled code.
+    Overlap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    Code(Instruction, Origin),
+    Data(u16, DataReason),
+}
+
+impl Entry {
+    pub fn length(&self) -> usize {
+        match self {
+            Self::Code(instruction, _) => instruction.byte_length(),
+            Self::Data(_, _) => 1,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Disassembly {
+    pub entries: BTreeMap<u16, Entry>,
+    pub jump_targets: BTreeSet<u16>,
+    pub call_targets: BTreeSet<u16>,
+}
+
+pub fn decode_at(program: &[u16], address: usize) -> Result<Instruction, DecoderError> {
+    let end = program.len().min(address + 4);
+    Instruction::try_from(&program[address.min(end)..end])
+}
+
+/// Returns the literal control flow targets of an instruction.
+pub fn branch_targets(instruction: &Instruction) -> Vec<u16> {
+    match instruction {
+        Instruction::Jump(target)
+        | Instruction::JumpIfNonZero(_, target)
+        | Instruction::JumpIfZero(_, target)
+        | Instruction::Call(target) => vec![*target],
+        _ => vec![],
+    }
+}
+
+/// Returns true if execution never continues with the next instruction.
+pub fn ends_flow(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Halt | Instruction::Jump(_) | Instruction::Return
+    )
+}
+
+/// The printed character of an `OUT` with a literal ASCII operand.
+fn character_comment(instruction: &Instruction) -> String {
+    match instruction {
+        Instruction::Out(character @ 0..=127) => format!(" {:?}", *character as u8 as char),
+        _ => String::new(),
+    }
+}
+
+fn format_line(address: u16, text: &str, comment: &str) -> String {
+    if comment.is_empty() {
+        format!("{:5}: {}", address, text)
+    } else {
+        format!("{:5}: {:24} ;{}", address, text, comment)
+    }
+}
+
+impl Disassembly {
+    pub fn new(program: &[u16]) -> Self {
+        let mut disassembly = Self::default();
+        disassembly.follow_control_flow(program, 0);
+        disassembly.sweep_linear(program);
+        disassembly.collect_targets();
+        disassembly
+    }
+
+    fn collect_targets(&mut self) {
+        for entry in self.entries.values() {
+            if let Entry::Code(instruction, _) = entry {
+                for target in branch_targets(instruction) {
+                    if !self.entries.contains_key(&target) {
+                        continue;
+                    }
+                    if matches!(instruction, Instruction::Call(_)) {
+                        self.call_targets.insert(target);
+                    } else {
+                        self.jump_targets.insert(target);
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_covered(&self, address: usize) -> bool {
+        self.entries
+            .range(..=address as u16)
+            .next_back()
+            .is_some_and(|(&start, entry)| address < start as usize + entry.length())
+    }
+
+    fn follow_control_flow(&mut self, program: &[u16], entry_point: u16) {
+        let mut queue = VecDeque::from([entry_point]);
+
+        while let Some(start) = queue.pop_front() {
+            let mut address = start as usize;
+            while address < program.len() && !self.is_covered(address) {
+                let Ok(instruction) = decode_at(program, address) else {
+                    break;
+                };
+                let end = address + instruction.byte_length();
+                if self
+                    .entries
+                    .range(address as u16..end as u16)
+                    .next()
+                    .is_some()
+                {
+                    // would overlap with already decoded instructions
+                    break;
+                }
+
+                for target in branch_targets(&instruction) {
+                    if (target as usize) < program.len() {
+                        queue.push_back(target);
+                    }
+                }
+
+                self.entries
+                    .insert(address as u16, Entry::Code(instruction, Origin::Reachable));
+                if ends_flow(&instruction) {
+                    break;
+                }
+                address = end;
+            }
+        }
+    }
+
+    fn sweep_linear(&mut self, program: &[u16]) {
+        let mut address = 0;
+        while address < program.len() {
+            if let Some(entry) = self.entries.get(&(address as u16)) {
+                address += entry.length();
+                continue;
+            }
+
+            let next_known = self
+                .entries
+                .range(address as u16..)
+                .next()
+                .map_or(program.len(), |(&next, _)| next as usize);
+
+            match decode_at(program, address) {
+                Ok(instruction) if address + instruction.byte_length() <= next_known => {
+                    self.entries
+                        .insert(address as u16, Entry::Code(instruction, Origin::Linear));
+                    address += instruction.byte_length();
+                }
+                Ok(_) => {
+                    self.entries.insert(
+                        address as u16,
+                        Entry::Data(program[address], DataReason::Overlap),
+                    );
+                    address += 1;
+                }
+                Err(error) => {
+                    self.entries.insert(
+                        address as u16,
+                        Entry::Data(program[address], DataReason::Undecodable(error)),
+                    );
+                    address += 1;
+                }
+            }
+        }
+    }
+
+    pub fn render_entry(address: u16, entry: &Entry) -> String {
+        let (text, comment) = match entry {
+            Entry::Code(instruction, origin) => {
+                let mut comment = character_comment(instruction);
+                if *origin == Origin::Linear {
+                    comment.push_str(" unreached");
+                }
+                (instruction.to_assembly(), comment)
+            }
+            Entry::Data(value, DataReason::Undecodable(error)) => {
+                (format!(".data {}", value), format!(" {:?}", error))
+            }
+            Entry::Data(value, DataReason::Overlap) => {
+                (format!(".data {}", value), " overlaps code".to_string())
+            }
+        };
+        format_line(address, &text, &comment)
+    }
+
+    /// Renders a single decoded word without a disassembly around it, so nothing is known
+    /// about its reachability, e.g. for the memory view of a running machine.
+    pub fn render_decoded(
+        address: u16,
+        value: u16,
+        decoded: &Result<Instruction, DecoderError>,
+    ) -> String {
+        match decoded {
+            Ok(instruction) => format_line(
+                address,
+                &instruction.to_assembly(),
+                &character_comment(instruction),
+            ),
+            Err(error) => Self::render_entry(
+                address,
+                &Entry::Data(value, DataReason::Undecodable(*error)),
+            ),
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        for (address, entry) in self.entries.iter() {
+            if self.call_targets.contains(address) {
+                writeln!(text, "\n; function {}", address).unwrap();
+            } else if self.jump_targets.contains(address) {
+                writeln!(text, "; label {}", address).unwrap();
+            }
+            writeln!(text, "{}", Self::render_entry(*address, entry)).unwrap();
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::opcodes::{DecoderError, Instruction};
+
+    use super::{DataReason, Disassembly, Entry, Origin};
+
+    #[test]
+    fn test_reachable_and_linear_code() {
+        // JMP 4, NOOP (unreached), OUT 'A', HALT
+        let program = [6, 4, 21, 21, 19, 65, 0];
+        let disassembly = Disassembly::new(&program);
+        let entries: Vec<(u16, Entry)> = disassembly
+            .entries
+            .iter()
+            .map(|(address, entry)| (*address, *entry))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                (0, Entry::Code(Instruction::Jump(4), Origin::Reachable)),
+                (2, Entry::Code(Instruction::Noop, Origin::Linear)),
+                (3, Entry::Code(Instruction::Noop, Origin::Linear)),
+                (4, Entry::Code(Instruction::Out(65), Origin::Reachable)),
+                (6, Entry::Code(Instruction::Halt, Origin::Reachable)),
+            ]
+        );
+        assert!(disassembly.jump_targets.contains(&4));
+    }
+
+    #[test]
+    fn test_data_fallback() {
+        // JMP 4, SET running into the OUT, OUT 'A', HALT, invalid opcode, OUT without operand
+        let program = [6, 4, 1, 21, 19, 65, 0, 30000, 19];
+        let disassembly = Disassembly::new(&program);
+        assert_eq!(disassembly.entries[&2], Entry::Data(1, DataReason::Overlap));
+        assert_eq!(
+            disassembly.entries[&3],
+            Entry::Code(Instruction::Noop, Origin::Linear)
+        );
+        assert_eq!(
+            disassembly.entries[&7],
+            Entry::Data(30000, DataReason::Undecodable(DecoderError::Invalid(30000)))
+        );
+        assert_eq!(
+            disassembly.entries[&8],
+            Entry::Data(19, DataReason::Undecodable(DecoderError::ParameterMissing))
+        );
+        assert!(disassembly
+            .render()
+            .contains("    2: .data 1                  ; overlaps code\n"));
+    }
+}
@@ -0,0 +1,8 @@
+/// Static disassembler for program images.
+pub mod disassembler;
+
+/// Assembler producing loadable program images.
+pub mod assembler;
+
+/// Basic block and call graph analysis.
+pub mod analysis;
@@ -1,11 +1,55 @@
-use crate::vm::subscription::{
-    VirtualMachineSubscription, VirtualMachineSubscriptionTick, VirtualMachineSubscriptionUpdate,
+use std::thread;
+
+use crate::{
+    game::{self, parser::GameState, teleporter},
+    tools::disassembler::decode_at,
+    vm::{
+        memory::HEAP_SIZE,
+        replay::{Replay, ReplayRunner, ReplayStep},
+        subscription::{
+            MemoryPatch, VirtualMachineSubscription, VirtualMachineSubscriptionTick,
+            VirtualMachineSubscriptionUpdate,
+        },
+    },
 };
 
 #[derive(Debug)]
 pub enum Page {
     Output,
     MemoryView,
+    Savestates,
+}
+
+/// How the Memory View shows the heap.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryViewMode {
+    #[default]
+    Decimal,
+    Hex,
+    /// Every word as a character, which reveals the strings of the program.
+    Ascii,
+    /// One decoded instruction per line.
+    Instructions,
+}
+
+impl MemoryViewMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Decimal => Self::Hex,
+            Self::Hex => Self::Ascii,
+            Self::Ascii => Self::Instructions,
+            Self::Instructions => Self::Decimal,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Decimal => "decimal",
+            Self::Hex => "hex",
+            Self::Ascii => "ASCII",
+            Self::Instructions => "instructions",
+        }
+    }
 }
 
 /// Application.
@@ -14,10 +58,22 @@ pub struct App {
     pub should_quit: bool,
     pub current_input: String,
     pub active_page: Page,
+    /// Scrolled lines of the Memory View, or the first address when showing instructions.
     pub memory_page_scroll: usize,
+    pub memory_view_mode: MemoryViewMode,
+    /// First address and mode of the last rendered Memory View, to keep the address when
+    /// switching modes.
+    pub memory_page_start: (usize, MemoryViewMode),
+    pub savestate_selection: usize,
     pub virtual_machine_subscription: VirtualMachineSubscription,
     pub next_tick_to_send: VirtualMachineSubscriptionTick,
     pub last_update: Box<VirtualMachineSubscriptionUpdate>,
+    /// Game state parsed from the output, refreshed when the output changes.
+    pub game_state: GameState,
+    /// Message of the viewer itself, shown until the machine reports a new one.
+    pub status_message: Option<String>,
+    /// Background search for r7, patched into the machine when it is done.
+    pub teleporter_search: Option<thread::JoinHandle<Vec<u16>>>,
 }
 
 impl App {
@@ -28,14 +84,21 @@ impl App {
             current_input: String::default(),
             active_page: Page::Output,
             memory_page_scroll: 0,
-            virtual_machine_subscription: virtual_machine_subscription,
+            memory_view_mode: MemoryViewMode::default(),
+            memory_page_start: (0, MemoryViewMode::default()),
+            savestate_selection: 0,
+            virtual_machine_subscription,
             next_tick_to_send: VirtualMachineSubscriptionTick::default(),
             last_update: Box::new(VirtualMachineSubscriptionUpdate::default()),
+            game_state: GameState::default(),
+            status_message: None,
+            teleporter_search: None,
         }
     }
 
     /// Handles the tick event of the terminal.
     pub fn tick(&mut self) {
+        self.poll_teleporter_search();
         let _ = self
             .virtual_machine_subscription
             .tick_sender
@@ -46,6 +109,12 @@ impl App {
 
     pub fn update(&mut self) {
         if let Ok(update) = self.virtual_machine_subscription.update_receiver.try_recv() {
+            if update.savestate.output_buffer != self.last_update.savestate.output_buffer {
+                self.game_state = GameState::from_output(&update.savestate.output_buffer);
+            }
+            if update.status_message != self.last_update.status_message {
+                self.status_message = None;
+            }
             self.last_update = update;
         }
     }
@@ -53,10 +122,109 @@ impl App {
     pub fn toggle_page(&mut self) {
         self.active_page = match self.active_page {
             Page::Output => Page::MemoryView,
-            Page::MemoryView => Page::Output,
+            Page::MemoryView => Page::Savestates,
+            Page::Savestates => Page::Output,
         }
     }
 
+    /// Scrolls the Memory View by a line, which is a whole instruction in instruction mode.
+    pub fn scroll_memory_down(&mut self) {
+        self.memory_page_scroll = match self.memory_view_mode {
+            MemoryViewMode::Instructions => {
+                let heap = &self.last_update.savestate.memory.heap;
+                let address = self.memory_page_scroll;
+                let length = decode_at(heap, address).map_or(1, |i| i.byte_length());
+                (address + length).min(HEAP_SIZE - 1)
+            }
+            _ => self.memory_page_scroll.saturating_add(1),
+        };
+    }
+
+    /// Scrolls the Memory View back by a line. In instruction mode this goes to the furthest
+    /// address up to four words back whose instruction ends at the current one, or one word.
+    pub fn scroll_memory_up(&mut self) {
+        self.memory_page_scroll = match self.memory_view_mode {
+            MemoryViewMode::Instructions => {
+                let heap = &self.last_update.savestate.memory.heap;
+                let address = self.memory_page_scroll;
+                (address.saturating_sub(4)..address)
+                    .find(|start| {
+                        decode_at(heap, *start)
+                            .is_ok_and(|instruction| start + instruction.byte_length() == address)
+                    })
+                    .unwrap_or(address.saturating_sub(1))
+            }
+            _ => self.memory_page_scroll.saturating_sub(1),
+        };
+    }
+
+    /// Patches the teleporter with the given r7, without one the search runs on a worker
+    /// thread and the patch is sent by `poll_teleporter_search` once it is done.
+    /// Places the coins of the inventory on the monument through a one step replay.
+    pub fn solve_coins(&mut self) {
+        let replay = Replay {
+            steps: vec![(0, ReplayStep::Solve("coins".to_string()))],
+        };
+        self.next_tick_to_send.replay =
+            Some(ReplayRunner::new(replay).with_solver(game::solve_puzzle));
+    }
+
+    pub fn patch_teleporter(&mut self, r7: Option<u16>) {
+        if let Some(r7) = r7 {
+            self.send_teleporter_patch(Some(r7));
+        } else if self.teleporter_search.is_some() {
+            self.status_message = Some("Still searching r7 for the teleporter".to_string());
+        } else {
+            self.teleporter_search = Some(thread::spawn(|| teleporter::solve(0)));
+            self.status_message = Some("Searching r7 for the teleporter".to_string());
+        }
+    }
+
+    fn poll_teleporter_search(&mut self) {
+        if !self
+            .teleporter_search
+            .as_ref()
+            .is_some_and(|search| search.is_finished())
+        {
+            return;
+        }
+        let values = self
+            .teleporter_search
+            .take()
+            .and_then(|search| search.join().ok())
+            .unwrap_or_default();
+        self.send_teleporter_patch(values.first().copied());
+    }
+
+    fn send_teleporter_patch(&mut self, r7: Option<u16>) {
+        let Some(r7) = r7 else {
+            self.status_message = Some("No r7 passes the teleporter confirmation".to_string());
+            return;
+        };
+        match teleporter::patch_writes(&self.last_update.savestate.memory.heap, r7) {
+            Some((call_site, writes)) => {
+                self.next_tick_to_send.patch = Some(MemoryPatch {
+                    writes,
+                    description: format!(
+                        "Set r7 to {} and patched the confirmation call at {}",
+                        r7, call_site.call
+                    ),
+                })
+            }
+            None => {
+                self.status_message = Some("Teleporter confirmation call not found".to_string())
+            }
+        }
+    }
+
+    /// Name of the slot currently selected in the savestate browser.
+    pub fn selected_savestate(&self) -> Option<String> {
+        self.last_update
+            .savestate_slots
+            .get(self.savestate_selection)
+            .map(|slot| slot.name.clone())
+    }
+
     /// Set should_quit to true to quit the application.
     pub fn quit(&mut self) {
         self.should_quit = true;
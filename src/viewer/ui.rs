@@ -1,4 +1,5 @@
 use crate::{
+    tools::disassembler::{decode_at, Disassembly},
     viewer::app::App,
     vm::memory::{HEAP_SIZE, REGISTER_ADDRESS_START},
 };
@@ -6,11 +7,12 @@ use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     prelude::Frame,
     style::{Color, Style},
+    text::{Line, Span, Text},
     widgets::{Block, BorderType, Borders, Paragraph, Wrap},
 };
 use std::fmt::Write;
 
-use super::app::Page;
+use super::app::{MemoryViewMode, Page};
 
 pub fn render(app: &mut App, f: &mut Frame) {
     let layout_main = Layout::default()
@@ -27,12 +29,29 @@ pub fn render(app: &mut App, f: &mut Frame) {
 
     match app.active_page {
         Page::Output => {
-            render_output(app, f, layout_output[0]);
+            let layout_game = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(1), Constraint::Length(36)])
+                .split(layout_output[0]);
+            let layout_side = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Min(1),
+                    Constraint::Length(app.last_update.codes.len() as u16 + 2),
+                ])
+                .split(layout_game[1]);
+            render_output(app, f, layout_game[0]);
+            render_game_state(app, f, layout_side[0]);
+            render_codes(app, f, layout_side[1]);
             render_input(app, f, layout_output[1]);
         }
         Page::MemoryView => {
             render_memory(app, f, layout_main[1]);
         }
+        Page::Savestates => {
+            render_savestates(app, f, layout_output[0]);
+            render_input(app, f, layout_output[1]);
+        }
     }
 }
 
@@ -46,9 +65,9 @@ pub fn render_cpu_state(app: &mut App, f: &mut Frame, size: Rect) {
         .iter()
         .enumerate()
     {
-        write!(
+        writeln!(
             registers,
-            "{} | {}: {}\n",
+            "{} | {}: {}",
             REGISTER_ADDRESS_START as usize + i,
             i,
             r
@@ -58,26 +77,48 @@ pub fn render_cpu_state(app: &mut App, f: &mut Frame, size: Rect) {
     let mut stack = String::new();
     for (i, (sv, sc)) in app.last_update.savestate.memory.stack.iter().enumerate() {
         if let Some(call) = sc {
-            write!(stack, "{:5}: {:6} | CALL {:5}\n", i, sv, call).unwrap();
+            writeln!(stack, "{:5}: {:6} | CALL {:5}", i, sv, call).unwrap();
         } else {
-            write!(stack, "{:5}: {:6}\n", i, sv).unwrap();
+            writeln!(stack, "{:5}: {:6}", i, sv).unwrap();
         }
     }
 
+    let mut breakpoints = String::new();
+    for breakpoint in app.last_update.savestate.breakpoints.entries.iter() {
+        writeln!(breakpoints, "{}", breakpoint).unwrap();
+    }
+
+    for watchpoint in app.last_update.savestate.memory.watchpoints.entries.iter() {
+        writeln!(breakpoints, "watch {}", watchpoint).unwrap();
+    }
+
+    let instruction = match app.last_update.current_instruction {
+        Ok(instruction) => instruction.to_string(),
+        Err(error) => format!("<{}>", error),
+    };
+    let fault = match app.last_update.fault {
+        Some(error) => format!("FAULT: {}\n", error),
+        None => String::new(),
+    };
+
     let mut widget = Paragraph::new(format!(
         "Cycle: {}
 ------ Execution -------
 PC: {}
 Instruction: {}
------- Registers -------
+{}------ Registers -------
+{}
+----- Breakpoints ------
 {}
 -------- Stack ---------
 {}
 ",
         app.last_update.savestate.cycle,
         app.last_update.savestate.program_counter,
-        app.last_update.current_instruction,
+        instruction,
+        fault,
         registers,
+        breakpoints,
         stack,
     ));
 
@@ -93,7 +134,7 @@ Instruction: {}
 }
 
 pub fn render_output(app: &mut App, f: &mut Frame, size: Rect) {
-    let mut widget = Paragraph::new(format!("{}", app.last_update.savestate.output_buffer,));
+    let mut widget = Paragraph::new(app.last_update.savestate.output_buffer.to_string());
     widget = widget.wrap(Wrap { trim: true });
     widget = widget.block(
         Block::default()
@@ -111,12 +152,92 @@ pub fn render_output(app: &mut App, f: &mut Frame, size: Rect) {
     f.render_widget(widget, size);
 }
 
+pub fn render_game_state(app: &mut App, f: &mut Frame, size: Rect) {
+    let state = &app.game_state;
+    let mut text = String::new();
+    match &state.room {
+        Some(room) => {
+            writeln!(text, "{}", room.title).unwrap();
+            writeln!(text, "------ Things ------").unwrap();
+            for thing in room.things.iter() {
+                writeln!(text, "- {}", thing).unwrap();
+            }
+            writeln!(text, "------ Exits -------").unwrap();
+            for exit in room.exits.iter() {
+                writeln!(text, "- {}", exit).unwrap();
+            }
+        }
+        None => writeln!(text, "No room seen yet").unwrap(),
+    }
+    writeln!(text, "---- Inventory -----").unwrap();
+    match &state.inventory {
+        Some(items) => {
+            for item in items.iter() {
+                writeln!(text, "- {}", item).unwrap();
+            }
+        }
+        None => writeln!(text, "(use 'inv')").unwrap(),
+    }
+    if let Some(command) = &state.last_command {
+        writeln!(text, "--- Last command ---\n{}", command).unwrap();
+    }
+
+    let mut widget = Paragraph::new(text);
+    widget = widget.wrap(Wrap { trim: true });
+    widget = widget.block(
+        Block::default()
+            .title(if state.awaiting_command {
+                "Game - waiting"
+            } else {
+                "Game"
+            })
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded),
+    );
+    widget = widget.style(Style::default().fg(Color::White));
+
+    f.render_widget(widget, size);
+}
+
+pub fn render_codes(app: &mut App, f: &mut Frame, size: Rect) {
+    let codes = &app.last_update.codes;
+    let mut lines = vec![];
+    for (i, code) in codes.iter().enumerate() {
+        let line = match code.label() {
+            Some(text) => Line::from(format!("#{} FOUND {}", i + 1, text)),
+            None => Line::from(format!("#{} X     {}", i + 1, code.hash))
+                .style(Style::default().fg(Color::DarkGray)),
+        };
+        lines.push(line);
+    }
+    let found = codes.iter().filter(|code| code.code.is_some()).count();
+
+    let mut widget = Paragraph::new(Text::from(lines));
+    widget = widget.block(
+        Block::default()
+            .title(format!("Codes {}/{}", found, codes.len()))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded),
+    );
+    widget = widget.style(Style::default().fg(Color::White));
+
+    f.render_widget(widget, size);
+}
+
 pub fn render_input(app: &mut App, f: &mut Frame, size: Rect) {
-    let mut widget = Paragraph::new(format!("{}", app.current_input));
+    let mut widget = Paragraph::new(app.current_input.to_string());
 
+    let status_message = app
+        .status_message
+        .as_ref()
+        .or(app.last_update.status_message.as_ref());
+    let title = match status_message {
+        Some(message) => format!("Input - {}", message),
+        None => "Input".to_string(),
+    };
     widget = widget.block(
         Block::default()
-            .title("Input")
+            .title(title)
             .borders(Borders::ALL)
             .border_type(BorderType::Rounded),
     );
@@ -126,41 +247,91 @@ pub fn render_input(app: &mut App, f: &mut Frame, size: Rect) {
 }
 
 pub fn render_memory(app: &mut App, f: &mut Frame, size: Rect) {
+    let mode = app.memory_view_mode;
     let line_width = (size.width - 2) as usize;
     let widget_height = (size.height - 2) as usize;
-    let memory_group_size = 7 * 5 + 2;
-    let memory_values_per_line = ((line_width - 6) / memory_group_size) * 5;
-    let memory_values_total = widget_height * memory_values_per_line;
+    let (memory_group_size, memory_cell_width) = match mode {
+        MemoryViewMode::Decimal => (5, 7),
+        MemoryViewMode::Hex => (8, 5),
+        MemoryViewMode::Ascii => (16, 1),
+        MemoryViewMode::Instructions => (1, 0),
+    };
+    // instructions have different lengths, there the scroll is the first address
+    let memory_values_per_line = match mode {
+        MemoryViewMode::Instructions => 1,
+        _ => {
+            let memory_group_width = memory_group_size * memory_cell_width + 2;
+            (line_width.saturating_sub(6) / memory_group_width).max(1) * memory_group_size
+        }
+    };
+    let memory_values_total = match mode {
+        MemoryViewMode::Instructions => 1,
+        _ => widget_height * memory_values_per_line,
+    };
+
+    let (previous_start, previous_mode) = app.memory_page_start;
+    if previous_mode != mode {
+        app.memory_page_scroll = previous_start / memory_values_per_line;
+    }
     if (app.memory_page_scroll * memory_values_per_line + memory_values_total) > HEAP_SIZE {
-        app.memory_page_scroll = (HEAP_SIZE - memory_values_total) / memory_values_per_line;
+        app.memory_page_scroll =
+            HEAP_SIZE.saturating_sub(memory_values_total) / memory_values_per_line;
     }
-
     let memory_page_start = app.memory_page_scroll * memory_values_per_line;
-    let memory_page_end = memory_page_start + memory_values_total;
+    app.memory_page_start = (memory_page_start, mode);
 
-    let mut text = String::new();
-    for (i, chunk) in app.last_update.savestate.memory.heap[memory_page_start..memory_page_end]
-        .chunks(memory_values_per_line)
-        .enumerate()
-    {
-        write!(
-            text,
-            "{:5} ",
-            (app.memory_page_scroll + i) * memory_values_per_line
-        )
-        .unwrap();
-        for memory_group in chunk.chunks(5) {
-            write!(text, " |").unwrap();
-            for memval in memory_group {
-                write!(text, " {:6}", memval).unwrap();
+    let heap = &app.last_update.savestate.memory.heap;
+    let program_counter = app.last_update.savestate.program_counter as usize;
+    let highlight = Style::default().fg(Color::Black).bg(Color::White);
+    let mut lines = vec![];
+
+    if mode == MemoryViewMode::Instructions {
+        let mut address = memory_page_start;
+        while lines.len() < widget_height && address < HEAP_SIZE {
+            let decoded = decode_at(heap, address);
+            let length = decoded.map_or(1, |instruction| instruction.byte_length());
+            let text = Disassembly::render_decoded(address as u16, heap[address], &decoded);
+            if (address..address + length).contains(&program_counter) {
+                lines.push(Line::from(Span::styled(text, highlight)));
+            } else {
+                lines.push(Line::from(text));
             }
+            address += length;
+        }
+    } else {
+        let memory_page_end = (memory_page_start + memory_values_total).min(HEAP_SIZE);
+        for (i, chunk) in heap[memory_page_start..memory_page_end]
+            .chunks(memory_values_per_line)
+            .enumerate()
+        {
+            let line_start = memory_page_start + i * memory_values_per_line;
+            let mut spans = vec![Span::raw(format!("{:5} ", line_start))];
+            for (address, memval) in (line_start..).zip(chunk) {
+                if (address - line_start).is_multiple_of(memory_group_size) {
+                    spans.push(Span::raw(" |"));
+                }
+                let text = match mode {
+                    MemoryViewMode::Hex => format!(" {:04x}", memval),
+                    MemoryViewMode::Ascii => match memval {
+                        32..=126 => (*memval as u8 as char).to_string(),
+                        _ => ".".to_string(),
+                    },
+                    _ => format!(" {:6}", memval),
+                };
+                if address == program_counter {
+                    spans.push(Span::styled(text, highlight));
+                } else {
+                    spans.push(Span::raw(text));
+                }
+            }
+            lines.push(Line::from(spans));
         }
-        writeln!(text).unwrap();
     }
-    let mut widget = Paragraph::new(format!("{}", text));
+
+    let mut widget = Paragraph::new(Text::from(lines));
     widget = widget.block(
         Block::default()
-            .title("Memory View")
+            .title(format!("Memory View - {} (F4 switches)", mode.name()))
             .borders(Borders::ALL)
             .border_type(BorderType::Rounded),
     );
@@ -168,3 +339,52 @@ pub fn render_memory(app: &mut App, f: &mut Frame, size: Rect) {
 
     f.render_widget(widget, size);
 }
+
+pub fn render_savestates(app: &mut App, f: &mut Frame, size: Rect) {
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(10)])
+        .split(size);
+
+    let slots = &app.last_update.savestate_slots;
+    if app.savestate_selection >= slots.len() {
+        app.savestate_selection = slots.len().saturating_sub(1);
+    }
+
+    let mut lines = vec![];
+    for (i, slot) in slots.iter().enumerate() {
+        let line = Line::from(format!(
+            "{:20} | Cycle: {:12} | PC: {:5}",
+            slot.name, slot.cycle, slot.program_counter
+        ));
+        if i == app.savestate_selection {
+            lines.push(line.style(Style::default().fg(Color::Black).bg(Color::White)));
+        } else {
+            lines.push(line);
+        }
+    }
+    let mut widget = Paragraph::new(Text::from(lines));
+    widget = widget.block(
+        Block::default()
+            .title("Savestates (F5 save, F9 load, F2 rename, Del delete)")
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded),
+    );
+    widget = widget.style(Style::default().fg(Color::White));
+    f.render_widget(widget, layout[0]);
+
+    let tail = slots
+        .get(app.savestate_selection)
+        .map(|slot| slot.output_tail.join("\n"))
+        .unwrap_or_default();
+    let mut widget = Paragraph::new(tail);
+    widget = widget.wrap(Wrap { trim: true });
+    widget = widget.block(
+        Block::default()
+            .title("Slot Output")
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded),
+    );
+    widget = widget.style(Style::default().fg(Color::White));
+    f.render_widget(widget, layout[1]);
+}
@@ -1,17 +1,33 @@
 use crossterm::event::{KeyCode, KeyEvent};
 
-use crate::viewer::app::App;
+use crate::{
+    viewer::app::{App, Page},
+    vm::{
+        breakpoints::BreakpointCommand,
+        memory::{AMOUNT_REGISTERS, MAX_ADDRESS, REGISTER_ADDRESS_START},
+        savestate::QUICKSAVE_SLOT,
+        watchpoints::WatchpointCommand,
+    },
+};
 
 pub fn update(app: &mut App, key_event: KeyEvent) {
+    if let Page::Savestates = app.active_page {
+        if update_savestates(app, key_event) {
+            return;
+        }
+    }
+
     match key_event.code {
         KeyCode::Esc => app.quit(),
         KeyCode::Tab => app.toggle_page(),
-        KeyCode::Up => app.memory_page_scroll = app.memory_page_scroll.saturating_sub(1),
-        KeyCode::Down => app.memory_page_scroll = app.memory_page_scroll.saturating_add(1),
-        KeyCode::F(5) => app.next_tick_to_send.save_state = true,
+        KeyCode::Up => app.scroll_memory_up(),
+        KeyCode::Down => app.scroll_memory_down(),
+        KeyCode::F(4) => app.memory_view_mode = app.memory_view_mode.next(),
+        KeyCode::F(5) => app.next_tick_to_send.save_state_slot = Some(QUICKSAVE_SLOT.to_string()),
         KeyCode::F(6) => app.next_tick_to_send.write_history = true,
+        KeyCode::F(7) => app.next_tick_to_send.step_back = true,
         KeyCode::F(8) => app.next_tick_to_send.step_once = true,
-        KeyCode::F(9) => app.next_tick_to_send.load_state = true,
+        KeyCode::F(9) => app.next_tick_to_send.load_state_slot = Some(QUICKSAVE_SLOT.to_string()),
         KeyCode::Enter => {
             if app.current_input.starts_with("!") {
                 handle_command(app, app.current_input.clone());
@@ -32,21 +48,111 @@ pub fn update(app: &mut App, key_event: KeyEvent) {
     };
 }
 
+/// Key bindings of the savestate browser, returns true if the key was handled.
+///
+/// F5 saves into the slot named in the input line (or the selected slot), F9 loads,
+/// F2 renames the selected slot to the input line and Delete removes it.
+pub fn update_savestates(app: &mut App, key_event: KeyEvent) -> bool {
+    let selected = app.selected_savestate();
+    let input = app.current_input.trim().to_string();
+
+    match key_event.code {
+        KeyCode::Up => app.savestate_selection = app.savestate_selection.saturating_sub(1),
+        KeyCode::Down => {
+            let last = app.last_update.savestate_slots.len().saturating_sub(1);
+            app.savestate_selection = app.savestate_selection.saturating_add(1).min(last);
+        }
+        KeyCode::F(5) => {
+            let name = if input.is_empty() {
+                selected.unwrap_or(QUICKSAVE_SLOT.to_string())
+            } else {
+                input
+            };
+            app.next_tick_to_send.save_state_slot = Some(name);
+            app.current_input = String::default();
+        }
+        KeyCode::F(9) => app.next_tick_to_send.load_state_slot = selected,
+        KeyCode::F(2) => {
+            if let Some(name) = selected.filter(|_| !input.is_empty()) {
+                app.next_tick_to_send.rename_state_slot = Some((name, input));
+                app.current_input = String::default();
+            }
+        }
+        KeyCode::Delete => app.next_tick_to_send.delete_state_slot = selected,
+        _ => return false,
+    }
+    true
+}
+
+/// Shows the expected arguments of a command whose arguments could not be parsed.
+fn show_usage(app: &mut App, usage: &str) {
+    app.status_message = Some(format!("Usage: {}", usage));
+}
+
 pub fn handle_command(app: &mut App, input: String) {
     let parts: Vec<&str> = input.split_whitespace().collect();
 
     match parts.first() {
         Some(&"!pause") => app.next_tick_to_send.toggle_pause = true,
+        Some(&"!reverse") => app.next_tick_to_send.reverse_continue = true,
+        Some(&"!break") => match BreakpointCommand::parse(&parts[1..]) {
+            Some(command) => app.next_tick_to_send.breakpoint_command = Some(command),
+            None => show_usage(
+                app,
+                "!break add <pc> [hits <n>] [if r<idx><op><value>] | rm|enable|disable <id> | list",
+            ),
+        },
+        Some(&"!watch") => match WatchpointCommand::parse(&parts[1..]) {
+            Some(command) => app.next_tick_to_send.watchpoint_command = Some(command),
+            None => show_usage(app, "!watch add <address|r0..r7> [r|w|rw] | rm <id> | list"),
+        },
+        Some(&"!trace") => match (parts.get(1), parts.get(2)) {
+            (Some(&"start"), Some(&path)) => {
+                app.next_tick_to_send.trace_start = Some(path.to_string())
+            }
+            (Some(&"stop"), _) => app.next_tick_to_send.trace_stop = true,
+            _ => show_usage(app, "!trace start <path> | stop"),
+        },
+        Some(&"!save") => match parts.get(1) {
+            Some(&name) => app.next_tick_to_send.save_state_slot = Some(name.to_string()),
+            None => show_usage(app, "!save <slot>"),
+        },
+        Some(&"!load") => match parts.get(1) {
+            Some(&name) => app.next_tick_to_send.load_state_slot = Some(name.to_string()),
+            None => show_usage(app, "!load <slot>"),
+        },
+        Some(&"!poke") => {
+            let address = parts
+                .get(1)
+                .and_then(|target| match target.strip_prefix('r') {
+                    Some(register) => register
+                        .parse::<u16>()
+                        .ok()
+                        .filter(|register| *register < AMOUNT_REGISTERS as u16)
+                        .map(|register| REGISTER_ADDRESS_START + register),
+                    None => target.parse::<u16>().ok().filter(|a| *a <= MAX_ADDRESS),
+                });
+            let value = parts.get(2).and_then(|value| value.parse::<u16>().ok());
+            match (address, value) {
+                (Some(address), Some(value)) => app.next_tick_to_send.poke = Some((address, value)),
+                _ => show_usage(app, "!poke <address|r0..r7> <value>"),
+            }
+        }
+        Some(&"!coins") => app.solve_coins(),
+        Some(&"!teleporter") => match parts.get(1).map(|value| value.parse::<u16>()) {
+            Some(Ok(r7)) => app.patch_teleporter(Some(r7)),
+            Some(Err(_)) => show_usage(app, "!teleporter [r7]"),
+            None => app.patch_teleporter(None),
+        },
         Some(&"!setr") => {
-            if let Some(&register_idx_str) = parts.iter().nth(1) {
-                if let Ok(register_idx) = register_idx_str.parse::<usize>() {
-                    if let Some(&register_value_str) = parts.iter().nth(2) {
-                        if let Ok(register_value) = register_value_str.parse::<u16>() {
-                            app.next_tick_to_send.set_register_id = Some(register_idx);
-                            app.next_tick_to_send.set_register_value = register_value;
-                        }
-                    }
+            let register_idx = parts.get(1).and_then(|idx| idx.parse::<usize>().ok());
+            let register_value = parts.get(2).and_then(|value| value.parse::<u16>().ok());
+            match (register_idx, register_value) {
+                (Some(register_idx), Some(register_value)) => {
+                    app.next_tick_to_send.set_register_id = Some(register_idx);
+                    app.next_tick_to_send.set_register_value = register_value;
                 }
+                _ => show_usage(app, "!setr <register> <value>"),
             }
         }
         _ => {}
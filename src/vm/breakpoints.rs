@@ -0,0 +1,347 @@
+use std::fmt::Display;
+
+use super::memory::AMOUNT_REGISTERS;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl Comparison {
+    const ALL: [(&'static str, Comparison); 6] = [
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        ("<=", Comparison::LessEqual),
+        (">=", Comparison::GreaterEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ];
+
+    pub fn symbol(&self) -> &'static str {
+        Self::ALL
+            .iter()
+            .find(|(_, comparison)| comparison == self)
+            .map(|(symbol, _)| *symbol)
+            .unwrap()
+    }
+
+    pub fn to_u8(self) -> u8 {
+        Self::ALL
+            .iter()
+            .position(|(_, comparison)| *comparison == self)
+            .unwrap() as u8
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Self::ALL
+            .get(value as usize)
+            .map(|(_, comparison)| *comparison)
+    }
+
+    pub fn evaluate(&self, left: u16, right: u16) -> bool {
+        match self {
+            Self::Equal => left == right,
+            Self::NotEqual => left != right,
+            Self::Less => left < right,
+            Self::LessEqual => left <= right,
+            Self::Greater => left > right,
+            Self::GreaterEqual => left >= right,
+        }
+    }
+}
+
+/// Condition on a register value, e.g. `r7==0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub register: usize,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Option<Self> {
+        let rest = text.strip_prefix('r')?;
+        let (symbol, comparison, position) =
+            Comparison::ALL.iter().find_map(|(symbol, comparison)| {
+                rest.find(symbol)
+                    .map(|position| (*symbol, *comparison, position))
+            })?;
+        let register = rest[..position].parse::<usize>().ok()?;
+        let value = rest[position + symbol.len()..].parse::<u16>().ok()?;
+
+        (register < AMOUNT_REGISTERS).then_some(Self {
+            register,
+            comparison,
+            value,
+        })
+    }
+
+    pub fn holds(&self, registers: &[u16; AMOUNT_REGISTERS]) -> bool {
+        self.comparison
+            .evaluate(registers[self.register], self.value)
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "r{}{}{}",
+            self.register,
+            self.comparison.symbol(),
+            self.value
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub id: usize,
+    pub address: u16,
+    pub enabled: bool,
+    /// Only trigger once the breakpoint was reached this many times.
+    pub hit_count: Option<usize>,
+    pub hits: usize,
+    pub condition: Option<Condition>,
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "#{} {} {}",
+            self.id,
+            self.address,
+            if self.enabled { "on" } else { "off" }
+        )?;
+        if let Some(hit_count) = self.hit_count {
+            write!(f, " hits {}/{}", self.hits, hit_count)?;
+        }
+        if let Some(condition) = self.condition {
+            write!(f, " if {}", condition)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Breakpoints {
+    pub entries: Vec<Breakpoint>,
+}
+
+impl Breakpoints {
+    pub fn add(
+        &mut self,
+        address: u16,
+        hit_count: Option<usize>,
+        condition: Option<Condition>,
+    ) -> usize {
+        let id = self.entries.iter().map(|b| b.id + 1).max().unwrap_or(0);
+        self.entries.push(Breakpoint {
+            id,
+            address,
+            enabled: true,
+            hit_count,
+            hits: 0,
+            condition,
+        });
+        id
+    }
+
+    pub fn remove(&mut self, id: usize) -> bool {
+        let length = self.entries.len();
+        self.entries.retain(|breakpoint| breakpoint.id != id);
+        length != self.entries.len()
+    }
+
+    pub fn set_enabled(&mut self, id: usize, enabled: bool) -> bool {
+        match self.entries.iter_mut().find(|b| b.id == id) {
+            Some(breakpoint) => {
+                breakpoint.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Checks all breakpoints at the given address and returns the first that triggers.
+    pub fn check(&mut self, address: u16, registers: &[u16; AMOUNT_REGISTERS]) -> Option<usize> {
+        let mut triggered = None;
+        for breakpoint in self.entries.iter_mut() {
+            if !breakpoint.enabled || breakpoint.address != address {
+                continue;
+            }
+            if breakpoint.condition.is_some_and(|c| !c.holds(registers)) {
+                continue;
+            }
+            breakpoint.hits += 1;
+            if breakpoint.hits >= breakpoint.hit_count.unwrap_or(0) {
+                triggered = triggered.or(Some(breakpoint.id));
+            }
+        }
+        triggered
+    }
+
+    /// Takes back the hits `check` counted for the same state, when the cycle leading to it is
+    /// undone.
+    pub fn uncheck(&mut self, address: u16, registers: &[u16; AMOUNT_REGISTERS]) {
+        for breakpoint in self.entries.iter_mut() {
+            if breakpoint.enabled
+                && breakpoint.address == address
+                && breakpoint.condition.is_none_or(|c| c.holds(registers))
+            {
+                breakpoint.hits = breakpoint.hits.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Returns the first enabled breakpoint matching the state without counting a hit.
+    pub fn find_match(&self, address: u16, registers: &[u16; AMOUNT_REGISTERS]) -> Option<usize> {
+        self.entries
+            .iter()
+            .find(|breakpoint| {
+                breakpoint.enabled
+                    && breakpoint.address == address
+                    && breakpoint.condition.is_none_or(|c| c.holds(registers))
+            })
+            .map(|breakpoint| breakpoint.id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakpointCommand {
+    Add {
+        address: u16,
+        hit_count: Option<usize>,
+        condition: Option<Condition>,
+    },
+    Remove(usize),
+    Enable(usize),
+    Disable(usize),
+    List,
+}
+
+impl BreakpointCommand {
+    /// Parses the arguments of `!break`:
+    /// `add <pc> [hits <n>] [if r<idx><op><value>]`, `rm <id>`, `enable <id>`,
+    /// `disable <id>` and `list`.
+    pub fn parse(arguments: &[&str]) -> Option<Self> {
+        let id = || arguments.get(1)?.parse::<usize>().ok();
+
+        match arguments.first() {
+            Some(&"add") => {
+                let address = arguments.get(1)?.parse::<u16>().ok()?;
+                let mut hit_count = None;
+                let mut condition = None;
+                let mut rest = arguments[2..].iter();
+                while let Some(&keyword) = rest.next() {
+                    match keyword {
+                        "hits" => hit_count = Some(rest.next()?.parse::<usize>().ok()?),
+                        "if" => {
+                            let text: String = rest.by_ref().copied().collect();
+                            condition = Some(Condition::parse(&text)?);
+                        }
+                        _ => return None,
+                    }
+                }
+                Some(Self::Add {
+                    address,
+                    hit_count,
+                    condition,
+                })
+            }
+            Some(&"rm") => Some(Self::Remove(id()?)),
+            Some(&"enable") => Some(Self::Enable(id()?)),
+            Some(&"disable") => Some(Self::Disable(id()?)),
+            Some(&"list") => Some(Self::List),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::{execution::StopReason, VirtualMachine};
+
+    use super::{BreakpointCommand, Comparison, Condition};
+
+    const R0: u16 = 32768;
+    const R1: u16 = 32769;
+
+    #[test]
+    fn test_parse_breakpoint_command() {
+        assert_eq!(
+            BreakpointCommand::parse(&["add", "6027", "hits", "2", "if", "r7", "!=", "0"]),
+            Some(BreakpointCommand::Add {
+                address: 6027,
+                hit_count: Some(2),
+                condition: Some(Condition {
+                    register: 7,
+                    comparison: Comparison::NotEqual,
+                    value: 0,
+                }),
+            })
+        );
+        assert_eq!(
+            BreakpointCommand::parse(&["add", "843"]),
+            Some(BreakpointCommand::Add {
+                address: 843,
+                hit_count: None,
+                condition: None,
+            })
+        );
+        assert_eq!(BreakpointCommand::parse(&["add", "843", "hits"]), None);
+        assert_eq!(BreakpointCommand::parse(&["add", "pc"]), None);
+        assert_eq!(
+            BreakpointCommand::parse(&["rm", "1"]),
+            Some(BreakpointCommand::Remove(1))
+        );
+        assert_eq!(BreakpointCommand::parse(&["rm"]), None);
+        assert_eq!(
+            BreakpointCommand::parse(&["list"]),
+            Some(BreakpointCommand::List)
+        );
+    }
+
+    #[test]
+    fn test_breakpoint_pauses() {
+        // ADD r0 r0 1, EQ r1 r0 5, JF r1 0, HALT
+        let program = [9, R0, R0, 1, 4, R1, R0, 5, 8, R1, 0, 0];
+        let mut vm = VirtualMachine::builder().program(&program).build();
+
+        vm.handle_breakpoint_command(BreakpointCommand::parse(&["add", "4"]).unwrap());
+        vm.handle_breakpoint_command(
+            BreakpointCommand::parse(&["add", "4", "hits", "2", "if", "r0>=2"]).unwrap(),
+        );
+        vm.handle_breakpoint_command(BreakpointCommand::List);
+        assert_eq!(
+            vm.status_message.as_deref(),
+            Some("Breakpoints: #0 4 on, #1 4 on hits 0/2 if r0>=2")
+        );
+
+        let outcome = vm.run_until_halt();
+        assert_eq!(outcome.reason, StopReason::Paused);
+        assert_eq!((vm.program_counter, vm.memory.registers[0]), (4, 1));
+        assert_eq!(vm.status_message.as_deref(), Some("Breakpoint #0 hit at 4"));
+
+        // a paused machine stays paused until resumed
+        assert_eq!(vm.run_until_halt().cycles, 0);
+        vm.handle_breakpoint_command(BreakpointCommand::Remove(0));
+        assert_eq!(vm.status_message.as_deref(), Some("Removed breakpoint #0"));
+        vm.resume();
+        let outcome = vm.run_until_halt();
+        assert_eq!(outcome.reason, StopReason::Paused);
+        assert_eq!((vm.program_counter, vm.memory.registers[0]), (4, 3));
+        assert_eq!(vm.status_message.as_deref(), Some("Breakpoint #1 hit at 4"));
+
+        vm.handle_breakpoint_command(BreakpointCommand::Remove(1));
+        vm.resume();
+        assert_eq!(vm.run_until_halt().reason, StopReason::Halted);
+        assert_eq!(vm.memory.registers[0], 5);
+    }
+}
@@ -0,0 +1,179 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use super::{
+    codes::CodeDetector,
+    devices::{BufferInput, BufferOutput, VirtualMachineInput, VirtualMachineOutput},
+    rewind::Rewind,
+    savestate::SAVESTATE_DIRECTORY,
+    subscription::VirtualMachineSubscriber,
+    transcript::Transcript,
+    VirtualMachine, HISTORY_FILE_PATH,
+};
+
+/// Configures the I/O and front-end of a `VirtualMachine`.
+///
+/// Without further setup the machine reads from an empty `BufferInput` and writes into a
+/// `BufferOutput`, so it never blocks and can be embedded in tests or solvers.
+#[derive(Debug, Default)]
+pub struct VirtualMachineBuilder {
+    program: Vec<u16>,
+    input: Option<Box<dyn VirtualMachineInput>>,
+    output: Option<Box<dyn VirtualMachineOutput>>,
+    subscriber: Option<VirtualMachineSubscriber>,
+    history_path: Option<PathBuf>,
+    savestate_directory: Option<PathBuf>,
+    transcript: Option<Transcript>,
+    code_hashes: Option<Vec<String>>,
+    rewind: bool,
+}
+
+impl VirtualMachineBuilder {
+    pub fn program(mut self, program: &[u16]) -> Self {
+        self.program = program.to_vec();
+        self
+    }
+
+    pub fn input(mut self, input: impl VirtualMachineInput + 'static) -> Self {
+        self.input = Some(Box::new(input));
+        self
+    }
+
+    pub fn output(mut self, output: impl VirtualMachineOutput + 'static) -> Self {
+        self.output = Some(Box::new(output));
+        self
+    }
+
+    /// Attaches the viewer, which then also provides the input through its ticks.
+    pub fn subscriber(mut self, subscriber: VirtualMachineSubscriber) -> Self {
+        self.subscriber = Some(subscriber);
+        self
+    }
+
+    /// Where the input history is written to, `./history.txt` by default.
+    pub fn history_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.history_path = Some(path.into());
+        self
+    }
+
+    /// Where the savestate slots are stored, `./savestates` by default.
+    pub fn savestate_directory(mut self, path: impl Into<PathBuf>) -> Self {
+        self.savestate_directory = Some(path.into());
+        self
+    }
+
+    /// Logs input and output of the session.
+    pub fn transcript(mut self, transcript: Transcript) -> Self {
+        self.transcript = Some(transcript);
+        self
+    }
+
+    /// Hashes of the codes to look for in the output, the README ones by default.
+    pub fn code_hashes(mut self, hashes: Vec<String>) -> Self {
+        self.code_hashes = Some(hashes);
+        self
+    }
+
+    /// Records the undo history for stepping back, which only the viewer needs.
+    pub fn rewind(mut self, enabled: bool) -> Self {
+        self.rewind = enabled;
+        self
+    }
+
+    pub fn build(self) -> VirtualMachine {
+        let mut vm = VirtualMachine {
+            step_once: Default::default(),
+            paused: Default::default(),
+            halted: Default::default(),
+            awaiting_input: Default::default(),
+            cycle: Default::default(),
+            program_counter: Default::default(),
+            memory: Default::default(),
+            breakpoints: Default::default(),
+            stdin_history: Default::default(),
+            stdin_buffer: Default::default(),
+            output_buffer: Default::default(),
+            input: self
+                .input
+                .unwrap_or_else(|| Box::new(BufferInput::default())),
+            output: self
+                .output
+                .unwrap_or_else(|| Box::new(BufferOutput::default())),
+            subscriber: self.subscriber,
+            save_states: BTreeMap::new(),
+            rewind: Rewind {
+                enabled: self.rewind,
+                ..Default::default()
+            },
+            trace: None,
+            replay: None,
+            history_path: self
+                .history_path
+                .unwrap_or_else(|| PathBuf::from(HISTORY_FILE_PATH)),
+            savestate_directory: self
+                .savestate_directory
+                .unwrap_or_else(|| PathBuf::from(SAVESTATE_DIRECTORY)),
+            transcript: self.transcript,
+            codes: self
+                .code_hashes
+                .map_or_else(CodeDetector::default, CodeDetector::new),
+            status_message: None,
+            fault: None,
+        };
+        vm.load_data(&self.program);
+        vm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::vm::{
+        devices::{BufferInput, BufferOutput},
+        execution::StopReason,
+        VirtualMachine, HISTORY_FILE_PATH,
+    };
+
+    const R0: u16 = 32768;
+    // IN r0, OUT r0, JMP 0
+    const ECHO: [u16; 6] = [20, R0, 19, R0, 6, 0];
+
+    #[test]
+    fn test_builder_defaults() {
+        let mut vm = VirtualMachine::builder().program(&ECHO).build();
+        assert_eq!(vm.memory.heap[..ECHO.len()], ECHO);
+        assert_eq!(vm.history_path, Path::new(HISTORY_FILE_PATH));
+        assert_eq!(vm.codes.codes.len(), 8);
+        assert!(!vm.rewind.enabled);
+
+        // the default input is empty, so the machine stops instead of blocking
+        let outcome = vm.run_until_halt();
+        assert_eq!(outcome.reason, StopReason::AwaitingInput);
+        assert_eq!(outcome.cycles, 0);
+    }
+
+    #[test]
+    fn test_builder_devices() {
+        let output = BufferOutput::default();
+        let mut vm = VirtualMachine::builder()
+            .program(&ECHO)
+            .input(BufferInput::new("look\ninv\n"))
+            .output(output.clone())
+            .history_path("session.txt")
+            .savestate_directory("slots")
+            .code_hashes(vec!["76EC2408E8FE3F1753C25DB51EFD8EB3".to_string()])
+            .rewind(true)
+            .build();
+        assert_eq!(vm.history_path, Path::new("session.txt"));
+        assert_eq!(vm.savestate_directory, Path::new("slots"));
+        assert_eq!(vm.codes.codes[0].hash, "76ec2408e8fe3f1753c25db51efd8eb3");
+        assert_eq!(vm.codes.codes.len(), 1);
+        assert!(vm.rewind.enabled);
+
+        let outcome = vm.run_until_halt();
+        assert_eq!(outcome.reason, StopReason::AwaitingInput);
+        assert_eq!(outcome.output, "look\ninv\n");
+        assert_eq!(output.take(), "look\ninv\n");
+        assert_eq!(vm.stdin_history, "look\ninv\n");
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    fmt::{Display, Write},
+    ops::RangeInclusive,
+};
+
+use md5::{Digest, Md5};
+
+/// MD5 hashes of the challenge codes in the order of the README table.
+pub const README_HASHES: [&str; 8] = [
+    "76ec2408e8fe3f1753c25db51efd8eb3",
+    "0e6aa7be1f68d930926d72b3741a145c",
+    "7997a3b2941eab92c1c0345d5747b420",
+    "186f842951c0dcfe8838af1e7222b7d4",
+    "2bf84e54b95ce97aefd9fc920451fc45",
+    "e09640936b3ef532b7b8e83ce8f125f4",
+    "4873cf6b76f62ac7d5a53605b2535a0c",
+    "d0c54d4ed7f943280ce3e19532dbb1a6",
+];
+
+/// Codes are 10 to 12 letters and digits, shorter and longer words are not hashed.
+const CODE_LENGTHS: RangeInclusive<usize> = 10..=12;
+const HASH_LENGTH: usize = 32;
+/// Letters turning into each other in a mirror, all others are read as they are.
+const MIRRORED_PAIRS: [(char, char); 2] = [('b', 'd'), ('p', 'q')];
+
+/// How a printed word was read to get the code.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    #[default]
+    Printed,
+    Reversed,
+    /// Reversed with the mirrored letters swapped, e.g. the code seen in the mirror.
+    Mirrored,
+}
+
+impl Transform {
+    const ALL: [Self; 3] = [Self::Printed, Self::Reversed, Self::Mirrored];
+
+    pub fn apply(&self, word: &str) -> String {
+        match self {
+            Self::Printed => word.to_string(),
+            Self::Reversed => word.chars().rev().collect(),
+            Self::Mirrored => word.chars().rev().map(mirror).collect(),
+        }
+    }
+}
+
+impl Display for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Printed => write!(f, "printed"),
+            Self::Reversed => write!(f, "reversed"),
+            Self::Mirrored => write!(f, "mirrored"),
+        }
+    }
+}
+
+fn mirror(character: char) -> char {
+    MIRRORED_PAIRS
+        .iter()
+        .find_map(|(left, right)| match character {
+            c if c == *left => Some(*right),
+            c if c == *right => Some(*left),
+            _ => None,
+        })
+        .unwrap_or(character)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedCode {
+    /// Lowercase hex MD5 hash of the code.
+    pub hash: String,
+    pub code: Option<String>,
+    /// How the code was read from the printed word.
+    pub transform: Transform,
+}
+
+impl ExpectedCode {
+    /// The found code, with the transform unless it was printed as it is.
+    pub fn label(&self) -> Option<String> {
+        let code = self.code.as_ref()?;
+        Some(match self.transform {
+            Transform::Printed => code.clone(),
+            transform => format!("{} ({})", code, transform),
+        })
+    }
+}
+
+/// Hashes code looking words of the output and remembers the ones matching an expected hash.
+#[derive(Debug, Clone)]
+pub struct CodeDetector {
+    pub codes: Vec<ExpectedCode>,
+    /// Letters and digits printed since the last other character.
+    word: String,
+    /// Index of the code found by the last pushed character.
+    last_found: Option<usize>,
+}
+
+impl Default for CodeDetector {
+    fn default() -> Self {
+        Self::new(README_HASHES.iter().map(|hash| hash.to_string()))
+    }
+}
+
+impl CodeDetector {
+    pub fn new(hashes: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            codes: hashes
+                .into_iter()
+                .map(|hash| ExpectedCode {
+                    hash: hash.to_lowercase(),
+                    code: None,
+                    transform: Transform::Printed,
+                })
+                .collect(),
+            word: String::new(),
+            last_found: None,
+        }
+    }
+
+    /// Hashes of a list with one hash per line or of the first column of a markdown table, so
+    /// the README itself works as a hash list.
+    pub fn parse_hashes(text: &str) -> Vec<String> {
+        text.lines()
+            .filter_map(|line| {
+                let line = line.trim().trim_start_matches('|');
+                let cell = line.split('|').next()?.trim().trim_matches('`');
+                (cell.len() == HASH_LENGTH && cell.chars().all(|c| c.is_ascii_hexdigit()))
+                    .then(|| cell.to_lowercase())
+            })
+            .collect()
+    }
+
+    /// Feeds one printed character, returns the index of a code found with it.
+    pub fn push(&mut self, character: char) -> Option<usize> {
+        self.last_found = None;
+        if character.is_ascii_alphanumeric() {
+            // a word one longer than a code is never one, no matter how it goes on
+            if self.word.len() <= *CODE_LENGTHS.end() {
+                self.word.push(character);
+            }
+            return None;
+        }
+        let word = std::mem::take(&mut self.word);
+        self.last_found = self.check(&word);
+        self.last_found
+    }
+
+    /// The unfinished word, to be restored with `undo_push`.
+    pub fn word(&self) -> &str {
+        &self.word
+    }
+
+    pub fn last_found(&self) -> Option<usize> {
+        self.last_found
+    }
+
+    /// Reverts a `push`, given the word before it and the code it found.
+    pub fn undo_push(&mut self, word: String, found: Option<usize>) {
+        self.word = word;
+        self.last_found = None;
+        if let Some(code) = found.and_then(|idx| self.codes.get_mut(idx)) {
+            code.code = None;
+            code.transform = Transform::default();
+        }
+    }
+
+    /// Feeds a whole text, returns the indices of the codes found in it.
+    pub fn scan(&mut self, text: &str) -> Vec<usize> {
+        text.chars()
+            .chain(['\n'])
+            .filter_map(|character| self.push(character))
+            .collect()
+    }
+
+    /// Hashes the word as printed and as seen in a mirror.
+    fn check(&mut self, word: &str) -> Option<usize> {
+        if !CODE_LENGTHS.contains(&word.len()) {
+            return None;
+        }
+        Transform::ALL.into_iter().find_map(|transform| {
+            let code = transform.apply(word);
+            let hash = md5_hex(&code);
+            let (idx, expected) = self
+                .codes
+                .iter_mut()
+                .enumerate()
+                .find(|(_, expected)| expected.code.is_none() && expected.hash == hash)?;
+            expected.code = Some(code);
+            expected.transform = transform;
+            Some(idx)
+        })
+    }
+
+    pub fn found(&self) -> usize {
+        self.codes.iter().filter(|code| code.code.is_some()).count()
+    }
+
+    /// The codes table of the README, with `FOUND` or `X` as status.
+    pub fn status_table(&self) -> String {
+        let mut table = String::from("| MD5 Hash | Status |\n| -------- | ------ |\n");
+        for code in self.codes.iter() {
+            let status = if code.code.is_some() { "FOUND" } else { "X" };
+            writeln!(table, "| `{}` | {} |", code.hash, status).unwrap();
+        }
+        table
+    }
+}
+
+fn md5_hex(text: &str) -> String {
+    Md5::digest(text.as_bytes())
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            write!(hex, "{:02x}", byte).unwrap();
+            hex
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CodeDetector, Transform, README_HASHES};
+
+    #[test]
+    fn test_detect_codes() {
+        let mut detector = CodeDetector::default();
+        let found = detector.scan(
+            "Please record your progress by putting codes like\n\
+             this one into the challenge website: ImoFztWQCvxj\n\
+             The self-test completion code is: BNCyODLfQkIl\n",
+        );
+        assert_eq!(found, vec![1, 2]);
+        assert_eq!(detector.codes[1].code.as_deref(), Some("ImoFztWQCvxj"));
+        assert_eq!(detector.found(), 2);
+        // a code is only reported once
+        assert!(detector.scan("ImoFztWQCvxj").is_empty());
+
+        let table = detector.status_table();
+        assert!(table.starts_with("| MD5 Hash | Status |\n| -------- | ------ |\n"));
+        assert!(table.contains("| `0e6aa7be1f68d930926d72b3741a145c` | FOUND |"));
+        assert!(table.contains("| `76ec2408e8fe3f1753c25db51efd8eb3` | X |"));
+
+        // the last code is read in a mirror
+        let found = detector.scan("you see \"iW8UwOHpH8op\" scrawled in charcoal");
+        assert_eq!(found, vec![7]);
+        assert_eq!(detector.codes[7].code.as_deref(), Some("qo8HqHOwU8Wi"));
+        assert_eq!(detector.codes[7].transform, Transform::Mirrored);
+
+        let readme = include_str!("../../README.md");
+        assert_eq!(CodeDetector::parse_hashes(readme), README_HASHES);
+    }
+}
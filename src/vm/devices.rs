@@ -0,0 +1,119 @@
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    io::{self, BufRead, Write},
+    sync::{Arc, Mutex},
+};
+
+/// Source of the text read by `IN`, asked for more whenever the stdin buffer runs empty.
+pub trait VirtualMachineInput: Debug + Send {
+    /// Returns the next chunk of input (usually a line), `None` if nothing is available.
+    fn next_line(&mut self) -> Option<String>;
+}
+
+/// Sink for the characters written by `OUT`.
+pub trait VirtualMachineOutput: Debug + Send {
+    fn write_char(&mut self, character: char);
+
+    fn flush(&mut self) {}
+}
+
+/// In-memory input, e.g. a replay or the commands of a solver.
+#[derive(Debug, Default, Clone)]
+pub struct BufferInput {
+    lines: VecDeque<String>,
+}
+
+impl BufferInput {
+    pub fn new(content: &str) -> Self {
+        let mut input = Self::default();
+        input.push(content);
+        input
+    }
+
+    pub fn push(&mut self, content: &str) {
+        self.lines
+            .extend(content.split_inclusive('\n').map(str::to_string));
+    }
+}
+
+impl VirtualMachineInput for BufferInput {
+    fn next_line(&mut self) -> Option<String> {
+        self.lines.pop_front()
+    }
+}
+
+/// Reads lines from the process stdin, `None` on EOF.
+#[derive(Debug, Default)]
+pub struct StdinInput;
+
+impl VirtualMachineInput for StdinInput {
+    fn next_line(&mut self) -> Option<String> {
+        let _ = io::stdout().flush();
+
+        let mut line = String::new();
+        match io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line),
+        }
+    }
+}
+
+/// In-memory output, clones share the same buffer so the caller can keep one to read from.
+#[derive(Debug, Default, Clone)]
+pub struct BufferOutput {
+    buffer: Arc<Mutex<String>>,
+}
+
+impl BufferOutput {
+    pub fn contents(&self) -> String {
+        self.buffer.lock().unwrap().clone()
+    }
+
+    /// Returns everything written so far and clears the buffer.
+    pub fn take(&self) -> String {
+        std::mem::take(&mut *self.buffer.lock().unwrap())
+    }
+}
+
+impl VirtualMachineOutput for BufferOutput {
+    fn write_char(&mut self, character: char) {
+        self.buffer.lock().unwrap().push(character);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct StdoutOutput;
+
+impl VirtualMachineOutput for StdoutOutput {
+    fn write_char(&mut self, character: char) {
+        // ignore errors so a closed pipe does not take the machine down
+        let _ = write!(io::stdout(), "{}", character);
+    }
+
+    fn flush(&mut self) {
+        let _ = io::stdout().flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BufferInput, BufferOutput, VirtualMachineInput, VirtualMachineOutput};
+
+    #[test]
+    fn test_buffer_devices() {
+        let mut input = BufferInput::new("take tablet\nuse");
+        input.push(" tablet\n");
+        assert_eq!(input.next_line().as_deref(), Some("take tablet\n"));
+        assert_eq!(input.next_line().as_deref(), Some("use"));
+        assert_eq!(input.next_line().as_deref(), Some(" tablet\n"));
+        assert_eq!(input.next_line(), None);
+
+        let output = BufferOutput::default();
+        let mut device = output.clone();
+        "ok\n".chars().for_each(|c| device.write_char(c));
+        assert_eq!(output.contents(), "ok\n");
+        assert_eq!(output.take(), "ok\n");
+        assert_eq!(device.contents(), "");
+    }
+}
@@ -0,0 +1,50 @@
+use std::fmt::Display;
+
+use super::opcodes::{format_operand, DecoderError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmError {
+    /// The words at the program counter do not decode to an instruction.
+    InvalidOpcode(DecoderError),
+    /// A value above the register range was used as an operand or address.
+    BadAddress(u16),
+    /// `POP` on an empty stack.
+    StackUnderflow,
+    /// `MOD` with a divisor of zero.
+    DivisionByZero,
+    /// An instruction tried to store its result into a literal instead of a register.
+    WriteToLiteral(u16),
+}
+
+impl Display for VmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidOpcode(DecoderError::Invalid(opcode)) => {
+                write!(f, "invalid opcode {}", opcode)
+            }
+            Self::InvalidOpcode(DecoderError::NotImplemented(opcode)) => {
+                write!(f, "opcode {} is not implemented", opcode)
+            }
+            Self::InvalidOpcode(DecoderError::ParameterMissing) => {
+                write!(f, "instruction runs past the end of memory")
+            }
+            Self::InvalidOpcode(DecoderError::Empty) => {
+                write!(f, "program counter outside of memory")
+            }
+            Self::BadAddress(address) => write!(f, "bad address {}", address),
+            Self::StackUnderflow => write!(f, "pop on an empty stack"),
+            Self::DivisionByZero => write!(f, "modulo by zero"),
+            Self::WriteToLiteral(address) => {
+                write!(f, "write to literal {}", format_operand(*address))
+            }
+        }
+    }
+}
+
+impl std::error::Error for VmError {}
+
+impl From<DecoderError> for VmError {
+    fn from(error: DecoderError) -> Self {
+        Self::InvalidOpcode(error)
+    }
+}
@@ -0,0 +1,141 @@
+use super::{error::VmError, opcodes::Instruction, VirtualMachine};
+
+/// Cycles `run_until_input` executes at most, booting the challenge takes about 700000.
+pub const INPUT_CYCLE_LIMIT: usize = 10_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The next instruction is an `IN` and no input is left.
+    AwaitingInput,
+    Halted,
+    /// The requested amount of cycles was executed.
+    CycleLimit,
+    /// `run_until_input` reached its cycle limit without the program asking for input, e.g.
+    /// in the unpatched teleporter check.
+    InputCycleLimit,
+    /// The program counter reached the requested address.
+    ReachedPc(u16),
+    /// A breakpoint or watchpoint paused the machine, see the status message.
+    Paused,
+    Fault(VmError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunOutcome {
+    pub reason: StopReason,
+    pub cycles: usize,
+    /// Output produced since the run was started.
+    pub output: String,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct RunLimits {
+    cycles: Option<usize>,
+    /// Like `cycles`, but reported as `InputCycleLimit`.
+    input_cycles: Option<usize>,
+    program_counter: Option<u16>,
+    /// Stop as soon as the stdin buffer is empty instead of asking the input device.
+    stop_on_empty_buffer: bool,
+}
+
+// Scripted execution
+impl VirtualMachine {
+    /// Runs until an `IN` finds the stdin buffer empty, without asking the input device.
+    ///
+    /// Gives up after `INPUT_CYCLE_LIMIT` cycles, so a program that never asks again cannot
+    /// hang a solver.
+    pub fn run_until_input(&mut self) -> RunOutcome {
+        self.run_until_input_within(INPUT_CYCLE_LIMIT)
+    }
+
+    /// `run_until_input` with a custom limit.
+    pub fn run_until_input_within(&mut self, cycles: usize) -> RunOutcome {
+        self.run_with_limits(RunLimits {
+            input_cycles: Some(cycles),
+            stop_on_empty_buffer: true,
+            ..Default::default()
+        })
+    }
+
+    pub fn run_for(&mut self, cycles: usize) -> RunOutcome {
+        self.run_with_limits(RunLimits {
+            cycles: Some(cycles),
+            ..Default::default()
+        })
+    }
+
+    pub fn run_until_pc(&mut self, program_counter: u16) -> RunOutcome {
+        self.run_with_limits(RunLimits {
+            program_counter: Some(program_counter),
+            ..Default::default()
+        })
+    }
+
+    /// Runs until the machine halts, feeding it from the input device until that is exhausted.
+    pub fn run_until_halt(&mut self) -> RunOutcome {
+        self.run_with_limits(RunLimits::default())
+    }
+
+    /// Clears a pause by a breakpoint, watchpoint or `#break`, the runs stop right away while
+    /// the machine is paused.
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    fn run_with_limits(&mut self, limits: RunLimits) -> RunOutcome {
+        let output_start = self.output_buffer.len();
+        let mut cycles = 0;
+
+        let reason = loop {
+            if self.halted {
+                break StopReason::Halted;
+            }
+            if self.paused {
+                break StopReason::Paused;
+            }
+            if limits.cycles.is_some_and(|limit| cycles >= limit) {
+                break StopReason::CycleLimit;
+            }
+            if limits.input_cycles.is_some_and(|limit| cycles >= limit) {
+                break StopReason::InputCycleLimit;
+            }
+            if !self.has_input_for_next_instruction(limits.stop_on_empty_buffer) {
+                self.awaiting_input = true;
+                break StopReason::AwaitingInput;
+            }
+            if let Err(error) = self.cycle() {
+                self.fault = Some(error);
+                break StopReason::Fault(error);
+            }
+            cycles += 1;
+            if limits.program_counter == Some(self.program_counter) {
+                break StopReason::ReachedPc(self.program_counter);
+            }
+        };
+
+        RunOutcome {
+            reason,
+            cycles,
+            output: self.output_buffer[output_start..].to_string(),
+        }
+    }
+
+    /// False if the next instruction is an `IN` that would not get any input.
+    fn has_input_for_next_instruction(&mut self, buffer_only: bool) -> bool {
+        if !self.stdin_buffer.is_empty()
+            || !matches!(self.decode(self.fetch()), Ok(Instruction::In(_)))
+        {
+            return true;
+        }
+        if buffer_only || self.subscriber.is_some() {
+            return false;
+        }
+        match self.input.next_line() {
+            Some(line) => {
+                self.prefeed_stdin(&line);
+                !self.stdin_buffer.is_empty()
+            }
+            None => false,
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use super::error::VmError;
+use super::watchpoints::{AccessKind, Watchpoints};
+
 pub const HEAP_SIZE: usize = 1 << 15; // 15-bit space
 pub const MAX_ADDRESS: u16 = HEAP_SIZE as u16 - 1;
 pub const AMOUNT_REGISTERS: usize = 8;
@@ -9,6 +12,7 @@ pub struct Memory {
     pub heap: [u16; HEAP_SIZE],
     pub registers: [u16; AMOUNT_REGISTERS],
     pub stack: Vec<(u16, Option<u16>)>, // value and Call
+    pub watchpoints: Watchpoints,
 }
 
 impl Default for Memory {
@@ -17,57 +21,88 @@ impl Default for Memory {
             heap: [0; HEAP_SIZE],
             registers: [0; AMOUNT_REGISTERS],
             stack: vec![],
+            watchpoints: Watchpoints::default(),
         }
     }
 }
 
 impl Memory {
-    pub fn read(&self, value: &u16) -> u16 {
+    /// Value of an operand, reading a register counts as an access for the watchpoints.
+    pub fn read(&mut self, value: &u16) -> Result<u16, VmError> {
+        let resolved = self.resolve(value)?;
+        if (REGISTER_ADDRESS_START..=REGISTER_ADDRESS_END).contains(value) {
+            self.record_access(*value, AccessKind::Read, resolved, resolved);
+        }
+        Ok(resolved)
+    }
+
+    /// Value of an operand without touching the watchpoints, e.g. for inspecting the machine.
+    pub fn resolve(&self, value: &u16) -> Result<u16, VmError> {
         match value {
-            0..=MAX_ADDRESS => *value,
+            0..=MAX_ADDRESS => Ok(*value),
             REGISTER_ADDRESS_START..=REGISTER_ADDRESS_END => {
                 let v = self.registers[*value as usize - HEAP_SIZE];
-                return if (REGISTER_ADDRESS_START..=REGISTER_ADDRESS_END).contains(&v) {
-                    self.read(&v)
+                if (REGISTER_ADDRESS_START..=REGISTER_ADDRESS_END).contains(&v) {
+                    self.resolve(&v)
                 } else {
-                    v
-                };
+                    Ok(v)
+                }
             }
-            _ => panic!("Read violation - read at {}", value),
+            _ => Err(VmError::BadAddress(*value)),
         }
     }
 
-    pub fn write(&mut self, address: &u16, value: u16) {
+    pub fn write(&mut self, address: &u16, value: u16) -> Result<(), VmError> {
         match address {
             REGISTER_ADDRESS_START..=REGISTER_ADDRESS_END => {
-                self.registers[*address as usize - HEAP_SIZE] =
-                    if (REGISTER_ADDRESS_START..=REGISTER_ADDRESS_END).contains(&value) {
-                        self.read(&value)
-                    } else {
-                        value
-                    };
+                let value = if (REGISTER_ADDRESS_START..=REGISTER_ADDRESS_END).contains(&value) {
+                    self.read(&value)?
+                } else {
+                    value
+                };
+                let register = &mut self.registers[*address as usize - HEAP_SIZE];
+                let old_value = std::mem::replace(register, value);
+                self.record_access(*address, AccessKind::Write, old_value, value);
+                Ok(())
             }
-            _ => panic!("Write violation - write {} at {}", value, address),
+            0..=MAX_ADDRESS => Err(VmError::WriteToLiteral(*address)),
+            _ => Err(VmError::BadAddress(*address)),
         }
     }
 
-    pub fn mem_read(&self, address: &u16) -> u16 {
-        match address {
+    pub fn mem_read(&mut self, address: &u16) -> Result<u16, VmError> {
+        let value = match address {
             0..=MAX_ADDRESS => self.heap[*address as usize],
             REGISTER_ADDRESS_START..=REGISTER_ADDRESS_END => {
                 self.registers[*address as usize - HEAP_SIZE]
             }
-            _ => panic!("Memory read violation - read at {}", address),
-        }
+            _ => return Err(VmError::BadAddress(*address)),
+        };
+        self.record_access(*address, AccessKind::Read, value, value);
+        Ok(value)
     }
 
-    pub fn mem_write(&mut self, address: &u16, value: u16) {
-        match address {
-            0..=MAX_ADDRESS => self.heap[*address as usize] = value,
+    pub fn mem_write(&mut self, address: &u16, value: u16) -> Result<(), VmError> {
+        let old_value = self.poke(address, value)?;
+        self.record_access(*address, AccessKind::Write, old_value, value);
+        Ok(())
+    }
+
+    /// Writes a heap cell or register without triggering watchpoints, returns the old value.
+    pub fn poke(&mut self, address: &u16, value: u16) -> Result<u16, VmError> {
+        let cell = match address {
+            0..=MAX_ADDRESS => &mut self.heap[*address as usize],
             REGISTER_ADDRESS_START..=REGISTER_ADDRESS_END => {
-                self.registers[*address as usize - HEAP_SIZE] = value
+                &mut self.registers[*address as usize - HEAP_SIZE]
             }
-            _ => panic!("Memory write violation - write {} at {}", value, address),
+            _ => return Err(VmError::BadAddress(*address)),
+        };
+        Ok(std::mem::replace(cell, value))
+    }
+
+    fn record_access(&mut self, address: u16, kind: AccessKind, old_value: u16, new_value: u16) {
+        if !self.watchpoints.entries.is_empty() {
+            self.watchpoints.record(address, kind, old_value, new_value);
         }
     }
 }
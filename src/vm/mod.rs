@@ -1,23 +1,53 @@
+pub mod breakpoints;
+pub mod builder;
+pub mod codes;
+pub mod devices;
+pub mod error;
+pub mod execution;
 pub mod memory;
 pub mod opcodes;
+pub mod replay;
+pub mod rewind;
+pub mod savestate;
 pub mod subscription;
-use memory::Memory;
-use opcodes::Instruction;
-use std::{collections::VecDeque, fs, thread};
+pub mod trace;
+pub mod transcript;
+pub mod watchpoints;
+use breakpoints::{BreakpointCommand, Breakpoints};
+use builder::VirtualMachineBuilder;
+use codes::CodeDetector;
+use devices::{VirtualMachineInput, VirtualMachineOutput};
+use error::VmError;
+use memory::{Memory, HEAP_SIZE};
+use opcodes::{format_operand, Instruction};
+use replay::{ReplayRunner, ReplayStatus};
+use rewind::Rewind;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs, io,
+    path::{Path, PathBuf},
+    thread,
+};
+use trace::TraceWriter;
+use transcript::Transcript;
+use watchpoints::WatchpointCommand;
 
+use self::savestate::{SavestateError, VirtualMachineSavestateSummary};
 use self::subscription::{
-    VirtualMachineSubscriber, VirtualMachineSubscriptionTick, VirtualMachineSubscriptionUpdate,
+    MemoryPatch, VirtualMachineSubscriber, VirtualMachineSubscriptionTick,
+    VirtualMachineSubscriptionUpdate,
 };
 
-pub const HISTORY_FILE_PATH: &'static str = "./history.txt";
+pub const HISTORY_FILE_PATH: &str = "./history.txt";
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct VirtualMachineSavestate {
     pub paused: bool,
     pub halted: bool,
     pub cycle: usize,
     pub program_counter: u16,
     pub memory: Memory,
+    pub breakpoints: Breakpoints,
     pub stdin_history: String,
     pub stdin_buffer: VecDeque<u8>,
     pub output_buffer: String,
@@ -28,32 +58,39 @@ pub struct VirtualMachine {
     pub step_once: bool,
     pub paused: bool,
     pub halted: bool,
+    pub awaiting_input: bool,
     pub cycle: usize,
     pub program_counter: u16,
     pub memory: Memory,
+    pub breakpoints: Breakpoints,
+    /// Input consumed by `IN` so far, the queued rest is in `stdin_buffer`.
     pub stdin_history: String,
     pub stdin_buffer: VecDeque<u8>,
     pub output_buffer: String,
-    pub subscriber: VirtualMachineSubscriber,
-    pub save_state: VirtualMachineSavestate,
+    pub input: Box<dyn VirtualMachineInput>,
+    pub output: Box<dyn VirtualMachineOutput>,
+    pub subscriber: Option<VirtualMachineSubscriber>,
+    pub save_states: BTreeMap<String, VirtualMachineSavestate>,
+    pub rewind: Rewind,
+    pub trace: Option<TraceWriter>,
+    /// Replay that is fed in before the viewer takes over the input.
+    pub replay: Option<ReplayRunner>,
+    /// Where the typed input is written to by `write_out_history`.
+    pub history_path: PathBuf,
+    /// Where the savestate slots are stored.
+    pub savestate_directory: PathBuf,
+    pub transcript: Option<Transcript>,
+    /// Codes recognized in the output so far.
+    pub codes: CodeDetector,
+    pub status_message: Option<String>,
+    /// Error of the last cycle, the machine stays paused on the faulting instruction.
+    pub fault: Option<VmError>,
 }
 
 // Creation & setup
 impl VirtualMachine {
-    pub fn new(subscriber: VirtualMachineSubscriber) -> Self {
-        Self {
-            step_once: Default::default(),
-            paused: Default::default(),
-            halted: Default::default(),
-            cycle: Default::default(),
-            program_counter: Default::default(),
-            memory: Default::default(),
-            stdin_history: Default::default(),
-            stdin_buffer: Default::default(),
-            output_buffer: Default::default(),
-            subscriber: subscriber,
-            save_state: VirtualMachineSavestate::default(),
-        }
+    pub fn builder() -> VirtualMachineBuilder {
+        VirtualMachineBuilder::default()
     }
 
     pub fn load_data(&mut self, program: &[u16]) {
@@ -61,109 +98,332 @@ impl VirtualMachine {
             self.memory.heap[offset] = *value;
         }
     }
+
+    /// Queues previously recorded input (e.g. a replay file) as if it was typed.
+    pub fn prefeed_stdin(&mut self, content: &str) {
+        for c in content.chars() {
+            self.stdin_buffer.push_back(c as u8);
+        }
+    }
 }
 
 // Run
 impl VirtualMachine {
-    pub fn get_stdin(&mut self) -> u8 {
+    pub fn get_stdin(&mut self) -> Option<u8> {
         while self.stdin_buffer.is_empty() {
-            self.handle_subscriber_blocking();
+            // a replay started from the viewer takes over the input at the top of `run`
+            if self.replay.is_some() {
+                return None;
+            }
+            if self.subscriber.is_some() {
+                self.handle_subscriber_blocking();
+                continue;
+            }
+            let line = self.input.next_line()?;
+            self.prefeed_stdin(&line);
         }
 
-        if let Some(character) = self.stdin_buffer.pop_front() {
-            return character;
+        let character = self.stdin_buffer.pop_front()?;
+        self.stdin_history.push(character as char);
+        if let Some(transcript) = &mut self.transcript {
+            transcript.record(character as char);
         }
-        panic!("did not get any characters")
+        Some(character)
     }
 
+    pub fn put_stdout(&mut self, character: char) {
+        self.output_buffer.push(character);
+        self.output.write_char(character);
+        if let Some(transcript) = &mut self.transcript {
+            transcript.record(character);
+        }
+        if let Some(idx) = self.codes.push(character) {
+            let code = self.codes.codes[idx].label().unwrap_or_default();
+            self.status_message = Some(format!(
+                "Found code #{} ({}/{}): {}",
+                idx + 1,
+                self.codes.found(),
+                self.codes.codes.len(),
+                code
+            ));
+        }
+    }
+
+    /// Up to four words starting at the program counter, fewer at the end of memory.
     pub fn fetch(&self) -> &[u16] {
-        &self.memory.heap[(self.program_counter as usize)..(self.program_counter as usize + 4)]
+        let start = (self.program_counter as usize).min(HEAP_SIZE);
+        let end = (start + 4).min(HEAP_SIZE);
+        &self.memory.heap[start..end]
     }
 
-    pub fn decode(&self, fetched_memory: &[u16]) -> Instruction {
-        match Instruction::try_from(fetched_memory) {
-            Ok(instruction) => instruction,
-            Err(x) => panic!("{:?}", x),
-        }
+    pub fn decode(&self, fetched_memory: &[u16]) -> Result<Instruction, VmError> {
+        Ok(Instruction::try_from(fetched_memory)?)
     }
 
-    pub fn execute(&mut self, instruction: Instruction) {
+    pub fn execute(&mut self, instruction: Instruction) -> Result<(), VmError> {
         instruction.execute(self)
     }
 
-    pub fn cycle(&mut self) {
-        self.handle_subscriber();
+    /// Executes a single instruction, recording the undo information for it if rewind is on.
+    ///
+    /// A failing instruction leaves the machine on it without recording a cycle.
+    pub fn step(&mut self) -> Result<(), VmError> {
+        self.record_checkpoint();
 
         let fetched_memory = self.fetch();
-        let instruction = self.decode(fetched_memory);
-        self.execute(instruction);
+        let instruction = self.decode(fetched_memory)?;
+
+        let trace_record = self
+            .trace
+            .is_some()
+            .then(|| self.begin_trace_record(&instruction));
+        let delta = self.rewind.enabled.then(|| self.record_delta(&instruction));
+        self.execute(instruction)?;
+        // an `IN` without input stays on the instruction, there is no cycle to count or undo
+        if matches!(instruction, Instruction::In(_)) && self.awaiting_input {
+            return Ok(());
+        }
+        if let Some(record) = trace_record {
+            self.finish_trace_record(record);
+        }
+        if let Some(delta) = delta {
+            self.finish_delta(delta, &instruction);
+        }
+
+        self.cycle += 1;
+        Ok(())
+    }
+
+    pub fn cycle(&mut self) -> Result<(), VmError> {
+        self.handle_subscriber();
+
+        let program_counter = self.program_counter;
+        if let Err(error) = self.step() {
+            self.memory.watchpoints.hits.clear();
+            return Err(error);
+        }
+        self.fault = None;
+        if self.awaiting_input {
+            return Ok(());
+        }
 
-        if self.program_counter == 5511 {
+        if !self.memory.watchpoints.hits.is_empty() {
+            let hits = std::mem::take(&mut self.memory.watchpoints.hits);
+            let messages: Vec<String> = hits
+                .into_iter()
+                .map(|mut hit| {
+                    hit.program_counter = program_counter;
+                    hit.to_string()
+                })
+                .collect();
             self.paused = true;
+            self.status_message = Some(messages.join(", "));
         }
 
-        self.cycle += 1;
+        if let Some(id) = self
+            .breakpoints
+            .check(self.program_counter, &self.memory.registers)
+        {
+            self.paused = true;
+            self.status_message = Some(format!(
+                "Breakpoint #{} hit at {}",
+                id, self.program_counter
+            ));
+        }
+        Ok(())
+    }
+
+    /// Pauses on the faulting instruction so it can be inspected, rewound or patched.
+    fn enter_fault(&mut self, error: VmError) {
+        self.paused = true;
+        self.fault = Some(error);
+        self.status_message = Some(format!("Fault at {}: {}", self.program_counter, error));
+    }
+
+    /// Advances the attached replay, it is kept around while paused by a `#break`.
+    fn continue_replay(&mut self) {
+        let Some(mut runner) = self.replay.take() else {
+            return;
+        };
+        match runner.run(self) {
+            Ok(ReplayStatus::Finished) => {
+                self.status_message = Some("Replay finished".to_string());
+            }
+            Ok(ReplayStatus::Break(line)) => {
+                self.status_message = Some(format!("Replay paused at line {}", line));
+                self.replay = Some(runner);
+            }
+            Ok(ReplayStatus::Paused) => self.replay = Some(runner),
+            Err(error) => {
+                self.paused = true;
+                self.status_message = Some(format!("Replay {}", error));
+            }
+        }
     }
 
     pub fn run(&mut self) {
         loop {
+            if !self.paused {
+                self.continue_replay();
+            }
             while (!self.halted && !self.paused) || self.step_once {
-                self.cycle();
+                if let Err(error) = self.cycle() {
+                    self.enter_fault(error);
+                }
                 thread::yield_now();
                 self.step_once = false;
+                // without a viewer nothing can provide the missing input anymore
+                if self.replay.is_some() || self.awaiting_input && self.subscriber.is_none() {
+                    break;
+                }
             }
 
-            self.handle_subscriber_blocking();
+            if self.subscriber.is_none() {
+                return;
+            }
+            if self.replay.is_none() || self.paused {
+                self.handle_subscriber_blocking();
+            }
         }
     }
+
+    /// Runs without a viewer until the machine halts or stdin is exhausted.
+    pub fn run_headless(&mut self) -> Result<(), VmError> {
+        // a scripted run may have stopped at an `IN`, the input device gets asked again
+        self.awaiting_input = false;
+        let result = loop {
+            if self.halted || self.awaiting_input {
+                break Ok(());
+            }
+            if let Err(error) = self.cycle() {
+                self.fault = Some(error);
+                break Err(error);
+            }
+        };
+        self.output.flush();
+        result
+    }
 }
 
 // Save&Load
 impl VirtualMachine {
     pub fn get_state(&mut self) -> VirtualMachineSavestate {
         VirtualMachineSavestate {
-            paused: self.paused.clone(),
-            halted: self.halted.clone(),
-            cycle: self.cycle.clone(),
-            program_counter: self.program_counter.clone(),
+            paused: self.paused,
+            halted: self.halted,
+            cycle: self.cycle,
+            program_counter: self.program_counter,
             stdin_history: self.stdin_history.clone(),
             stdin_buffer: self.stdin_buffer.clone(),
             memory: self.memory.clone(),
+            breakpoints: self.breakpoints.clone(),
             output_buffer: self.output_buffer.clone(),
         }
     }
 
-    pub fn load_state(&mut self) {
-        self.paused = self.save_state.paused.clone();
-        self.halted = self.save_state.halted.clone();
-        self.cycle = self.save_state.cycle.clone();
-        self.program_counter = self.save_state.program_counter.clone();
-        self.stdin_history = self.save_state.stdin_history.clone();
-        self.stdin_buffer = self.save_state.stdin_buffer.clone();
-        self.memory = self.save_state.memory.clone();
-        self.output_buffer = self.save_state.output_buffer.clone();
+    /// Restores a savestate, discarding the recorded rewind history.
+    pub fn apply_state(&mut self, state: VirtualMachineSavestate) {
+        self.rewind.clear();
+        self.fault = None;
+        self.paused = state.paused;
+        self.halted = state.halted;
+        self.awaiting_input = false;
+        self.cycle = state.cycle;
+        self.program_counter = state.program_counter;
+        self.stdin_history = state.stdin_history;
+        self.stdin_buffer = state.stdin_buffer;
+        let watchpoints = std::mem::take(&mut self.memory.watchpoints);
+        self.memory = state.memory;
+        self.memory.watchpoints = watchpoints;
+        self.breakpoints = state.breakpoints;
+        self.output_buffer = state.output_buffer;
+    }
+
+    /// Saves the current state into a named slot, which is also persisted to disk.
+    pub fn save_state_to_slot(&mut self, name: &str) -> Result<(), SavestateError> {
+        let state = self.get_state();
+        state.write_to_slot(&self.savestate_directory, name)?;
+        self.save_states.insert(name.to_string(), state);
+        Ok(())
+    }
+
+    /// Loads a named slot, reading it from disk if it is not known yet.
+    pub fn load_state_from_slot(&mut self, name: &str) -> Result<(), SavestateError> {
+        if !self.save_states.contains_key(name) {
+            let state = VirtualMachineSavestate::read_from_slot(&self.savestate_directory, name)?;
+            self.save_states.insert(name.to_string(), state);
+        }
+        self.apply_state(self.save_states[name].clone());
+        Ok(())
+    }
+
+    pub fn rename_state_slot(&mut self, name: &str, new_name: &str) -> Result<(), SavestateError> {
+        savestate::rename_slot(&self.savestate_directory, name, new_name)?;
+        if let Some(state) = self.save_states.remove(name) {
+            self.save_states.insert(new_name.to_string(), state);
+        }
+        Ok(())
     }
 
-    pub fn write_out_history(&self) {
-        fs::write(HISTORY_FILE_PATH, self.stdin_history.clone()).expect("Could not write file");
+    pub fn delete_state_slot(&mut self, name: &str) -> Result<(), SavestateError> {
+        savestate::delete_slot(&self.savestate_directory, name)?;
+        self.save_states.remove(name);
+        Ok(())
+    }
+
+    /// Reads all slots stored on disk, skipping ones that fail to load.
+    pub fn load_slots_from_disk(&mut self) {
+        for name in savestate::list_slots(&self.savestate_directory).unwrap_or_default() {
+            if let Ok(state) =
+                VirtualMachineSavestate::read_from_slot(&self.savestate_directory, &name)
+            {
+                self.save_states.insert(name, state);
+            }
+        }
+    }
+
+    pub fn get_slot_summaries(&self) -> Vec<VirtualMachineSavestateSummary> {
+        self.save_states
+            .iter()
+            .map(|(name, state)| state.summary(name))
+            .collect()
+    }
+
+    pub fn write_out_history(&self) -> io::Result<()> {
+        fs::write(&self.history_path, &self.stdin_history)
     }
 }
 
 // Subscriber
 impl VirtualMachine {
     pub fn handle_subscriber(&mut self) {
-        if let Ok(tick) = self.subscriber.tick_receiver.try_recv() {
+        let Some(subscriber) = &self.subscriber else {
+            return;
+        };
+        if let Ok(tick) = subscriber.tick_receiver.try_recv() {
             self.handle_subscriber_tick(tick);
-            let update = self.get_subscription_update();
-            let _ = self.subscriber.update_sender.send(update);
+            self.send_subscription_update();
         }
     }
 
+    /// Waits for the next tick, detaches the subscriber once the viewer is gone.
     pub fn handle_subscriber_blocking(&mut self) {
-        if let Ok(tick) = self.subscriber.tick_receiver.recv() {
-            self.handle_subscriber_tick(tick);
-            let update = self.get_subscription_update();
-            let _ = self.subscriber.update_sender.send(update);
+        let Some(subscriber) = &self.subscriber else {
+            return;
+        };
+        match subscriber.tick_receiver.recv() {
+            Ok(tick) => {
+                self.handle_subscriber_tick(tick);
+                self.send_subscription_update();
+            }
+            Err(_) => self.subscriber = None,
+        }
+    }
+
+    fn send_subscription_update(&mut self) {
+        let update = self.get_subscription_update();
+        if let Some(subscriber) = &self.subscriber {
+            let _ = subscriber.update_sender.send(update);
         }
     }
 
@@ -171,19 +431,64 @@ impl VirtualMachine {
         for c in tick.additional_stdin.chars() {
             self.output_buffer.push(c);
             self.stdin_buffer.push_back(c as u8);
-            self.stdin_history.push(c);
         }
 
-        if tick.save_state {
-            self.save_state = self.get_state();
+        if let Some(name) = tick.save_state_slot {
+            self.status_message = Some(match self.save_state_to_slot(&name) {
+                Ok(()) => format!("Saved slot '{}'", name),
+                Err(error) => format!("Could not save slot '{}': {}", name, error),
+            });
+        }
+
+        if let Some(name) = tick.load_state_slot {
+            self.status_message = Some(match self.load_state_from_slot(&name) {
+                Ok(()) => format!("Loaded slot '{}'", name),
+                Err(error) => format!("Could not load slot '{}': {}", name, error),
+            });
+        }
+
+        if let Some((name, new_name)) = tick.rename_state_slot {
+            self.status_message = Some(match self.rename_state_slot(&name, &new_name) {
+                Ok(()) => format!("Renamed slot '{}' to '{}'", name, new_name),
+                Err(error) => format!("Could not rename slot '{}': {}", name, error),
+            });
+        }
+
+        if let Some(name) = tick.delete_state_slot {
+            self.status_message = Some(match self.delete_state_slot(&name) {
+                Ok(()) => format!("Deleted slot '{}'", name),
+                Err(error) => format!("Could not delete slot '{}': {}", name, error),
+            });
         }
 
-        if tick.load_state {
-            self.load_state();
+        if let Some(command) = tick.breakpoint_command {
+            self.handle_breakpoint_command(command);
+        }
+
+        if let Some(command) = tick.watchpoint_command {
+            self.handle_watchpoint_command(command);
+        }
+
+        if let Some(path) = tick.trace_start {
+            self.status_message = Some(match self.start_trace(Path::new(&path)) {
+                Ok(()) => format!("Tracing into '{}'", path),
+                Err(error) => format!("Could not start trace '{}': {}", path, error),
+            });
+        }
+
+        if tick.trace_stop {
+            self.status_message = Some(match self.stop_trace() {
+                Ok(()) => "Trace stopped".to_string(),
+                Err(error) => format!("Could not finish trace: {}", error),
+            });
         }
 
         if tick.write_history {
-            self.write_out_history();
+            let path = self.history_path.display().to_string();
+            self.status_message = Some(match self.write_out_history() {
+                Ok(()) => format!("Wrote history to '{}'", path),
+                Err(error) => format!("Could not write history '{}': {}", path, error),
+            });
         }
 
         if tick.toggle_pause {
@@ -191,14 +496,133 @@ impl VirtualMachine {
         }
 
         if let Some(register_idx) = tick.set_register_id {
-            if register_idx <= self.memory.registers.len() {
+            if register_idx < self.memory.registers.len() {
                 self.memory.registers[register_idx] = tick.set_register_value;
             }
         }
 
+        if let Some((address, value)) = tick.poke {
+            self.status_message = Some(match self.memory.poke(&address, value) {
+                Ok(old_value) => format!(
+                    "Poked {}: {} -> {}",
+                    format_operand(address),
+                    old_value,
+                    value
+                ),
+                Err(error) => format!("Could not poke: {}", error),
+            });
+        }
+
+        if let Some(runner) = tick.replay {
+            self.status_message = Some(match self.replay {
+                Some(_) => "Wait for the running replay to finish".to_string(),
+                None => {
+                    self.replay = Some(runner);
+                    "Started the replay".to_string()
+                }
+            });
+        }
+
+        if let Some(patch) = tick.patch {
+            self.apply_patch(patch);
+        }
+
         if tick.step_once {
             self.step_once = true;
         }
+
+        if tick.step_back {
+            self.fault = None;
+            self.status_message = Some(match self.step_back() {
+                true => format!("Stepped back to cycle {}", self.cycle),
+                false => "No more history to step back".to_string(),
+            });
+            // the restored state may be a running one, stay on it for inspection
+            self.paused = true;
+        }
+
+        if tick.reverse_continue {
+            self.fault = None;
+            self.status_message = Some(match self.reverse_continue() {
+                Some(id) => format!("Reversed to breakpoint #{} at {}", id, self.program_counter),
+                None => format!("Reversed to start of history at cycle {}", self.cycle),
+            });
+            self.paused = true;
+        }
+    }
+
+    pub fn handle_breakpoint_command(&mut self, command: BreakpointCommand) {
+        let message = match command {
+            BreakpointCommand::Add {
+                address,
+                hit_count,
+                condition,
+            } => {
+                let id = self.breakpoints.add(address, hit_count, condition);
+                format!("Added breakpoint #{} at {}", id, address)
+            }
+            BreakpointCommand::Remove(id) => match self.breakpoints.remove(id) {
+                true => format!("Removed breakpoint #{}", id),
+                false => format!("No breakpoint #{}", id),
+            },
+            BreakpointCommand::Enable(id) => match self.breakpoints.set_enabled(id, true) {
+                true => format!("Enabled breakpoint #{}", id),
+                false => format!("No breakpoint #{}", id),
+            },
+            BreakpointCommand::Disable(id) => match self.breakpoints.set_enabled(id, false) {
+                true => format!("Disabled breakpoint #{}", id),
+                false => format!("No breakpoint #{}", id),
+            },
+            BreakpointCommand::List => {
+                let entries: Vec<String> = self
+                    .breakpoints
+                    .entries
+                    .iter()
+                    .map(|breakpoint| breakpoint.to_string())
+                    .collect();
+                format!("Breakpoints: {}", entries.join(", "))
+            }
+        };
+        self.status_message = Some(message);
+    }
+
+    /// Pokes all cells of the patch, stopping at the first invalid address.
+    fn apply_patch(&mut self, patch: MemoryPatch) {
+        let result = patch
+            .writes
+            .iter()
+            .try_for_each(|(address, value)| self.memory.poke(address, *value).map(|_| ()));
+        self.status_message = Some(match result {
+            Ok(()) => patch.description,
+            Err(error) => format!("Could not patch: {}", error),
+        });
+    }
+
+    pub fn handle_watchpoint_command(&mut self, command: WatchpointCommand) {
+        let watchpoints = &mut self.memory.watchpoints;
+        let message = match command {
+            WatchpointCommand::Add {
+                address,
+                on_read,
+                on_write,
+            } => {
+                let id = watchpoints.add(address, on_read, on_write);
+                format!("Added watchpoint #{} at {}", id, format_operand(address))
+            }
+            WatchpointCommand::Remove(id) => match watchpoints.remove(id) {
+                true => format!("Removed watchpoint #{}", id),
+                false => format!("No watchpoint #{}", id),
+            },
+            WatchpointCommand::List => {
+                let entries: Vec<String> = watchpoints
+                    .entries
+                    .iter()
+                    .map(|watchpoint| watchpoint.to_string())
+                    .collect();
+                format!("Watchpoints: {}", entries.join(", "))
+            }
+        };
+        self.status_message = Some(message);
     }
 
     pub fn get_subscription_update(&mut self) -> Box<VirtualMachineSubscriptionUpdate> {
@@ -206,25 +630,118 @@ impl VirtualMachine {
         let instruction = self.decode(fetched_memory);
         Box::new(VirtualMachineSubscriptionUpdate {
             current_instruction: instruction,
+            fault: self.fault,
             savestate: self.get_state(),
+            savestate_slots: self.get_slot_summaries(),
+            status_message: self.status_message.clone(),
+            codes: self.codes.codes.clone(),
         })
     }
 }
 
-/*
 #[cfg(test)]
 mod tests {
-    use super::VirtualMachine;
+    use crate::transform_bytes_to_program_code;
+
+    use super::{
+        devices::{BufferInput, BufferOutput},
+        execution::StopReason,
+        VirtualMachine,
+    };
 
     #[test]
     fn test_load_program_into_memory() {
         let program: [u16; 6] = [9, 32768, 32769, 4, 19, 32768];
-        let mut vm = VirtualMachine::default();
+        let mut vm = VirtualMachine::builder().build();
         vm.load_data(&program);
 
         for (offset, value) in program.iter().enumerate() {
             assert_eq!(vm.memory.heap[offset], *value);
         }
     }
+
+    #[test]
+    fn test_spec_example_program() {
+        let mut vm = VirtualMachine::builder().build();
+        vm.load_data(&[9, 32768, 32769, 4, 19, 32768, 0]);
+        vm.memory.registers[1] = 61;
+        while !vm.halted {
+            vm.cycle().unwrap();
+        }
+        assert_eq!(vm.output_buffer, "A");
+    }
+
+    #[test]
+    fn test_in_without_input() {
+        let mut vm = VirtualMachine::builder()
+            .program(&[20, 32768, 0])
+            .rewind(true)
+            .build();
+        vm.step().unwrap();
+        assert!(vm.awaiting_input);
+        assert_eq!((vm.cycle, vm.program_counter), (0, 0));
+        assert!(vm.rewind.deltas.is_empty());
+
+        vm.prefeed_stdin("x");
+        vm.step().unwrap();
+        assert!(!vm.awaiting_input);
+        assert_eq!((vm.cycle, vm.program_counter), (1, 2));
+        assert_eq!(vm.rewind.deltas.len(), 1);
+    }
+
+    #[test]
+    fn test_challenge_self_test() {
+        let program =
+            transform_bytes_to_program_code(include_bytes!("../../payloads/challenge.bin"))
+                .unwrap();
+        let output = BufferOutput::default();
+        let mut vm = VirtualMachine::builder()
+            .program(&program)
+            .output(output.clone())
+            .build();
+
+        let outcome = vm.run_until_input();
+        assert_eq!(outcome.reason, StopReason::AwaitingInput);
+        assert_eq!(outcome.output, output.take());
+        assert!(outcome.output.contains("Executing self-test..."));
+        assert!(outcome
+            .output
+            .contains("self-test complete, all tests pass"));
+        assert!(outcome.output.contains("== Foothills =="));
+
+        vm.prefeed_stdin("take tablet\n");
+        let outcome = vm.run_until_input();
+        assert_eq!(outcome.reason, StopReason::AwaitingInput);
+        assert!(outcome.output.contains("Taken."));
+        assert!(!outcome.output.contains("Foothills"));
+    }
+
+    #[test]
+    fn test_run_limits() {
+        let mut vm = VirtualMachine::builder()
+            .program(&[21, 21, 21, 19, 65, 20, 32768, 0])
+            .input(BufferInput::new("x"))
+            .build();
+
+        let outcome = vm.run_for(2);
+        assert_eq!(outcome.reason, StopReason::CycleLimit);
+        assert_eq!((outcome.cycles, vm.program_counter), (2, 2));
+
+        let outcome = vm.run_until_pc(3);
+        assert_eq!(outcome.reason, StopReason::ReachedPc(3));
+
+        let outcome = vm.run_until_input();
+        assert_eq!(outcome.reason, StopReason::AwaitingInput);
+        assert_eq!(outcome.output, "A");
+
+        let outcome = vm.run_until_halt();
+        assert_eq!(outcome.reason, StopReason::Halted);
+        assert_eq!(vm.memory.registers[0], 'x' as u16);
+
+        // JMP 0 never asks for input
+        let mut vm = VirtualMachine::builder().program(&[6, 0]).build();
+        let outcome = vm.run_until_input_within(1000);
+        assert_eq!(outcome.reason, StopReason::InputCycleLimit);
+        assert_eq!(outcome.cycles, 1000);
+    }
 }
-*/
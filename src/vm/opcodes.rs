@@ -1,11 +1,12 @@
 use std::fmt::Display;
 
 use crate::vm::{
-    memory::{HEAP_SIZE, MAX_ADDRESS},
+    error::VmError,
+    memory::{HEAP_SIZE, MAX_ADDRESS, REGISTER_ADDRESS_END, REGISTER_ADDRESS_START},
     VirtualMachine,
 };
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instruction {
     Halt,
     Set(u16, u16),
@@ -31,7 +32,7 @@ pub enum Instruction {
     Noop,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DecoderError {
     Invalid(u16),
     NotImplemented(u16),
@@ -43,9 +44,9 @@ impl TryFrom<&[u16]> for Instruction {
     type Error = DecoderError;
 
     fn try_from(value: &[u16]) -> Result<Self, Self::Error> {
-        let a = value.iter().nth(1).ok_or(Self::Error::ParameterMissing);
-        let b = value.iter().nth(2).ok_or(Self::Error::ParameterMissing);
-        let c = value.iter().nth(3).ok_or(Self::Error::ParameterMissing);
+        let a = value.get(1).ok_or(Self::Error::ParameterMissing);
+        let b = value.get(2).ok_or(Self::Error::ParameterMissing);
+        let c = value.get(3).ok_or(Self::Error::ParameterMissing);
 
         match value.first() {
             Some(0) => Ok(Self::Halt),
@@ -104,6 +105,44 @@ impl Instruction {
             Self::Noop => "NOOP",
         }
     }
+
+    pub fn operands(&self) -> Vec<u16> {
+        match self {
+            Self::Halt | Self::Return | Self::Noop => vec![],
+            Self::Push(a)
+            | Self::Pop(a)
+            | Self::Jump(a)
+            | Self::Call(a)
+            | Self::Out(a)
+            | Self::In(a) => {
+                vec![*a]
+            }
+            Self::Set(a, b)
+            | Self::JumpIfNonZero(a, b)
+            | Self::JumpIfZero(a, b)
+            | Self::Not(a, b)
+            | Self::Load(a, b)
+            | Self::Store(a, b) => vec![*a, *b],
+            Self::Equality(a, b, c)
+            | Self::GreaterThan(a, b, c)
+            | Self::Add(a, b, c)
+            | Self::Mult(a, b, c)
+            | Self::Mod(a, b, c)
+            | Self::And(a, b, c)
+            | Self::Or(a, b, c) => vec![*a, *b, *c],
+        }
+    }
+
+    /// Renders the instruction as `MNEMONIC op1 op2 ...` with registers shown as `r0..r7`.
+    pub fn to_assembly(&self) -> String {
+        let mut result = self.memnonic().to_string();
+        for operand in self.operands() {
+            result.push(' ');
+            result.push_str(&format_operand(operand));
+        }
+        result
+    }
+
     pub fn byte_length(&self) -> usize {
         match self {
             Self::Halt => 1,
@@ -131,7 +170,7 @@ impl Instruction {
         }
     }
 
-    pub fn execute(&self, vm: &mut VirtualMachine) {
+    pub fn execute(&self, vm: &mut VirtualMachine) -> Result<(), VmError> {
         match self {
             // 0
             Self::Halt => {
@@ -139,56 +178,58 @@ impl Instruction {
             }
             // 1
             Self::Set(register, value) => {
-                vm.memory.write(register, *value);
+                vm.memory.write(register, *value)?;
                 vm.program_counter += self.byte_length() as u16;
             }
             // 2
             Self::Push(value) => {
-                vm.memory.stack.push((vm.memory.read(value), None));
+                let value = vm.memory.read(value)?;
+                vm.memory.stack.push((value, None));
                 vm.program_counter += self.byte_length() as u16;
             }
             // 3
             Self::Pop(register) => {
-                let (value, _) = vm.memory.stack.pop().unwrap();
-                vm.memory.write(register, value);
+                let (value, _) = *vm.memory.stack.last().ok_or(VmError::StackUnderflow)?;
+                vm.memory.write(register, value)?;
+                vm.memory.stack.pop();
                 vm.program_counter += self.byte_length() as u16;
             }
             // 4
             Self::Equality(address, operand1, operand2) => {
-                let result = if vm.memory.read(operand1) == vm.memory.read(operand2) {
+                let result = if vm.memory.read(operand1)? == vm.memory.read(operand2)? {
                     1
                 } else {
                     0
                 };
-                vm.memory.write(address, result);
+                vm.memory.write(address, result)?;
                 vm.program_counter += self.byte_length() as u16;
             }
             // 5
             Self::GreaterThan(address, operand1, operand2) => {
-                let result = if vm.memory.read(operand1) > vm.memory.read(operand2) {
+                let result = if vm.memory.read(operand1)? > vm.memory.read(operand2)? {
                     1
                 } else {
                     0
                 };
-                vm.memory.write(address, result);
+                vm.memory.write(address, result)?;
                 vm.program_counter += self.byte_length() as u16;
             }
             // 6
             Self::Jump(address) => {
-                vm.program_counter = vm.memory.read(address);
+                vm.program_counter = vm.memory.read(address)?;
             }
             // 7
             Self::JumpIfNonZero(compare, jump_address) => {
-                if vm.memory.read(compare) != 0 {
-                    vm.program_counter = vm.memory.read(jump_address);
+                if vm.memory.read(compare)? != 0 {
+                    vm.program_counter = vm.memory.read(jump_address)?;
                 } else {
                     vm.program_counter += self.byte_length() as u16;
                 }
             }
             // 8
             Self::JumpIfZero(compare, jump_address) => {
-                if vm.memory.read(compare) == 0 {
-                    vm.program_counter = vm.memory.read(jump_address);
+                if vm.memory.read(compare)? == 0 {
+                    vm.program_counter = vm.memory.read(jump_address)?;
                 } else {
                     vm.program_counter += self.byte_length() as u16;
                 }
@@ -196,85 +237,113 @@ impl Instruction {
             // 9
             Self::Add(address, operand1, operand2) => {
                 let mut result =
-                    vm.memory.read(operand1) as usize + vm.memory.read(operand2) as usize;
+                    vm.memory.read(operand1)? as usize + vm.memory.read(operand2)? as usize;
                 result %= HEAP_SIZE;
-                vm.memory.write(address, result as u16);
+                vm.memory.write(address, result as u16)?;
                 vm.program_counter += self.byte_length() as u16;
             }
             // 10
             Self::Mult(address, operand1, operand2) => {
                 let mut result =
-                    vm.memory.read(operand1) as usize * vm.memory.read(operand2) as usize;
+                    vm.memory.read(operand1)? as usize * vm.memory.read(operand2)? as usize;
                 result %= HEAP_SIZE;
-                vm.memory.write(address, result as u16);
+                vm.memory.write(address, result as u16)?;
                 vm.program_counter += self.byte_length() as u16;
             }
             // 11
             Self::Mod(address, operand1, operand2) => {
-                let result = vm.memory.read(operand1) % vm.memory.read(operand2);
-                vm.memory.write(address, result);
+                let result = vm
+                    .memory
+                    .read(operand1)?
+                    .checked_rem(vm.memory.read(operand2)?)
+                    .ok_or(VmError::DivisionByZero)?;
+                vm.memory.write(address, result)?;
                 vm.program_counter += self.byte_length() as u16;
             }
             // 12
             Self::And(address, operand1, operand2) => {
-                let result = vm.memory.read(operand1) & vm.memory.read(operand2);
-                vm.memory.write(address, result);
+                let result = vm.memory.read(operand1)? & vm.memory.read(operand2)?;
+                vm.memory.write(address, result)?;
                 vm.program_counter += self.byte_length() as u16;
             }
             // 13
             Self::Or(address, operand1, operand2) => {
-                let result = vm.memory.read(operand1) | vm.memory.read(operand2);
-                vm.memory.write(address, result);
+                let result = vm.memory.read(operand1)? | vm.memory.read(operand2)?;
+                vm.memory.write(address, result)?;
                 vm.program_counter += self.byte_length() as u16;
             }
             // 14
             Self::Not(address, operand) => {
-                let result = !vm.memory.read(operand) & MAX_ADDRESS;
-                vm.memory.write(address, result);
+                let result = !vm.memory.read(operand)? & MAX_ADDRESS;
+                vm.memory.write(address, result)?;
                 vm.program_counter += self.byte_length() as u16;
             }
             // 15
             Self::Load(register, address) => {
-                let mem_value = vm.memory.mem_read(&vm.memory.read(address));
-                vm.memory.write(register, mem_value);
+                let source = vm.memory.read(address)?;
+                let mem_value = vm.memory.mem_read(&source)?;
+                vm.memory.write(register, mem_value)?;
                 vm.program_counter += self.byte_length() as u16;
             }
             // 16
             Self::Store(address, register_or_value) => {
-                let value = vm.memory.read(register_or_value);
-                vm.memory.mem_write(&vm.memory.read(address), value);
+                let value = vm.memory.read(register_or_value)?;
+                let target = vm.memory.read(address)?;
+                vm.memory.mem_write(&target, value)?;
                 vm.program_counter += self.byte_length() as u16;
             }
             // 17
             Self::Call(address) => {
-                vm.memory.stack.push((
-                    vm.program_counter + self.byte_length() as u16,
-                    Some(vm.memory.read(address)),
-                ));
-                vm.program_counter = vm.memory.read(address);
+                let target = vm.memory.read(address)?;
+                vm.memory
+                    .stack
+                    .push((vm.program_counter + self.byte_length() as u16, Some(target)));
+                vm.program_counter = target;
             }
             // 18
-            Self::Return => {
-                let (value, _) = vm.memory.stack.pop().unwrap();
-                vm.program_counter = value;
-            }
+            Self::Return => match vm.memory.stack.pop() {
+                Some((value, _)) => vm.program_counter = value,
+                // an empty stack on return halts the machine
+                None => vm.halted = true,
+            },
             // 19
             Self::Out(character_raw) => {
-                let character = vm.memory.read(character_raw) as u8 as char;
-                vm.output_buffer.push(character);
+                let character = vm.memory.read(character_raw)? as u8 as char;
+                vm.put_stdout(character);
                 vm.program_counter += self.byte_length() as u16;
             }
             // 20
             Self::In(address) => {
-                let character = vm.get_stdin() as u16;
-                vm.memory.write(address, character);
-                vm.program_counter += self.byte_length() as u16;
+                if !(REGISTER_ADDRESS_START..=REGISTER_ADDRESS_END).contains(address) {
+                    return Err(match address {
+                        0..=MAX_ADDRESS => VmError::WriteToLiteral(*address),
+                        _ => VmError::BadAddress(*address),
+                    });
+                }
+                if let Some(character) = vm.get_stdin() {
+                    vm.awaiting_input = false;
+                    vm.memory.write(address, character as u16)?;
+                    vm.program_counter += self.byte_length() as u16;
+                } else {
+                    vm.awaiting_input = true;
+                }
             }
             // 21
             Self::Noop => {
                 vm.program_counter += self.byte_length() as u16;
             }
         }
+        Ok(())
+    }
+}
+
+/// Formats a raw operand, showing register addresses as `r0..r7`.
+pub fn format_operand(value: u16) -> String {
+    match value {
+        REGISTER_ADDRESS_START..=REGISTER_ADDRESS_END => {
+            format!("r{}", value - REGISTER_ADDRESS_START)
+        }
+        _ => value.to_string(),
     }
 }
 
@@ -284,13 +353,21 @@ impl Display for Instruction {
     }
 }
 
-/*
 #[cfg(test)]
 mod tests {
-    use crate::vm::VirtualMachine;
+    use crate::vm::{error::VmError, VirtualMachine};
 
     use super::{DecoderError, Instruction};
 
+    const R0: u16 = 32768;
+    const R1: u16 = 32769;
+    const R2: u16 = 32770;
+
+    fn execute(vm: &mut VirtualMachine, instruction: Instruction) -> Result<(), VmError> {
+        vm.program_counter = 100;
+        instruction.execute(vm)
+    }
+
     #[test]
     fn test_construct_instruction() {
         let program: [u16; 5] = [21, 19, 65, 0, 200];
@@ -308,48 +385,201 @@ mod tests {
             Instruction::try_from(&program[4..5]),
             Err(DecoderError::Invalid(200))
         );
+        assert_eq!(
+            Instruction::try_from(&[9, R0, 1][..]),
+            Err(DecoderError::ParameterMissing)
+        );
+        assert_eq!(Instruction::try_from(&[][..]), Err(DecoderError::Empty));
+    }
+
+    #[test]
+    fn test_instruction_halt_and_noop() {
+        let mut vm = VirtualMachine::builder().build();
+        execute(&mut vm, Instruction::Noop).unwrap();
+        assert_eq!(vm.program_counter, 101);
+        assert!(!vm.halted);
+
+        execute(&mut vm, Instruction::Halt).unwrap();
+        assert!(vm.halted);
+    }
+
+    #[test]
+    fn test_instruction_set() {
+        let mut vm = VirtualMachine::builder().build();
+        execute(&mut vm, Instruction::Set(R0, 1234)).unwrap();
+        assert_eq!(vm.memory.registers[0], 1234);
+        assert_eq!(vm.program_counter, 103);
+
+        execute(&mut vm, Instruction::Set(R1, R0)).unwrap();
+        assert_eq!(vm.memory.registers[1], 1234);
+
+        assert_eq!(
+            execute(&mut vm, Instruction::Set(5, 1)),
+            Err(VmError::WriteToLiteral(5))
+        );
+        assert_eq!(vm.program_counter, 100);
+    }
+
+    #[test]
+    fn test_register_indirection() {
+        let mut vm = VirtualMachine::builder().build();
+        vm.memory.registers[0] = 42;
+        vm.memory.registers[1] = R0;
+        assert_eq!(vm.memory.read(&7), Ok(7));
+        assert_eq!(vm.memory.read(&R0), Ok(42));
+        assert_eq!(vm.memory.read(&R1), Ok(42));
+        assert_eq!(vm.memory.read(&32776), Err(VmError::BadAddress(32776)));
+    }
+
+    #[test]
+    fn test_instructions_stack() {
+        let mut vm = VirtualMachine::builder().build();
+        vm.memory.registers[0] = 7;
+        execute(&mut vm, Instruction::Push(R0)).unwrap();
+        execute(&mut vm, Instruction::Push(9)).unwrap();
+        assert_eq!(vm.memory.stack, vec![(7, None), (9, None)]);
+        assert_eq!(vm.program_counter, 102);
+
+        execute(&mut vm, Instruction::Pop(R1)).unwrap();
+        execute(&mut vm, Instruction::Pop(R2)).unwrap();
+        assert_eq!(vm.memory.registers[1], 9);
+        assert_eq!(vm.memory.registers[2], 7);
+        assert!(vm.memory.stack.is_empty());
+
+        assert_eq!(
+            execute(&mut vm, Instruction::Pop(R0)),
+            Err(VmError::StackUnderflow)
+        );
     }
 
     #[test]
-    fn test_instruction_halt() {
-        let mut vm = VirtualMachine::default();
-        let program: [u16; 1] = [0];
-        vm.load_data(&program);
-        let instruction = Instruction::Halt;
-        instruction.execute(&mut vm);
+    fn test_instructions_call_return() {
+        let mut vm = VirtualMachine::builder().build();
+        execute(&mut vm, Instruction::Call(300)).unwrap();
+        assert_eq!(vm.program_counter, 300);
+        assert_eq!(vm.memory.stack, vec![(102, Some(300))]);
+
+        vm.memory.registers[0] = 500;
+        vm.program_counter = 300;
+        Instruction::Call(R0).execute(&mut vm).unwrap();
+        assert_eq!(vm.program_counter, 500);
+        assert_eq!(vm.memory.stack.last(), Some(&(302, Some(500))));
+
+        Instruction::Return.execute(&mut vm).unwrap();
+        assert_eq!(vm.program_counter, 302);
+        Instruction::Return.execute(&mut vm).unwrap();
+        assert_eq!(vm.program_counter, 102);
+        assert!(!vm.halted);
+
+        Instruction::Return.execute(&mut vm).unwrap();
         assert!(vm.halted);
     }
 
+    #[test]
+    fn test_instructions_compare() {
+        let mut vm = VirtualMachine::builder().build();
+        execute(&mut vm, Instruction::Equality(R0, 3, 3)).unwrap();
+        assert_eq!(vm.memory.registers[0], 1);
+        assert_eq!(vm.program_counter, 104);
+        execute(&mut vm, Instruction::Equality(R0, 3, 4)).unwrap();
+        assert_eq!(vm.memory.registers[0], 0);
+
+        execute(&mut vm, Instruction::GreaterThan(R0, 4, 3)).unwrap();
+        assert_eq!(vm.memory.registers[0], 1);
+        execute(&mut vm, Instruction::GreaterThan(R0, 3, 3)).unwrap();
+        assert_eq!(vm.memory.registers[0], 0);
+    }
+
     #[test]
     fn test_instructions_jump() {
-        let mut vm = VirtualMachine::default();
-        let program: [u16; 2] = [0, 11];
-        vm.load_data(&program);
+        let mut vm = VirtualMachine::builder().build();
 
-        vm.program_counter = 100;
-        let instruction = Instruction::Jump(300);
-        instruction.execute(&mut vm);
+        execute(&mut vm, Instruction::Jump(300)).unwrap();
         assert_eq!(vm.program_counter, 300);
 
-        vm.program_counter = 100;
-        let instruction = Instruction::JumpIfNonZero(0, 300);
-        instruction.execute(&mut vm);
+        execute(&mut vm, Instruction::JumpIfNonZero(0, 300)).unwrap();
         assert_eq!(vm.program_counter, 103);
 
-        vm.program_counter = 100;
-        let instruction = Instruction::JumpIfNonZero(1, 300);
-        instruction.execute(&mut vm);
+        execute(&mut vm, Instruction::JumpIfNonZero(1, 300)).unwrap();
         assert_eq!(vm.program_counter, 300);
 
-        vm.program_counter = 100;
-        let instruction = Instruction::JumpIfZero(0, 300);
-        instruction.execute(&mut vm);
+        execute(&mut vm, Instruction::JumpIfZero(0, 300)).unwrap();
         assert_eq!(vm.program_counter, 300);
 
-        vm.program_counter = 100;
-        let instruction = Instruction::JumpIfZero(1, 300);
-        instruction.execute(&mut vm);
+        execute(&mut vm, Instruction::JumpIfZero(1, 300)).unwrap();
         assert_eq!(vm.program_counter, 103);
+
+        vm.memory.registers[0] = 400;
+        execute(&mut vm, Instruction::Jump(R0)).unwrap();
+        assert_eq!(vm.program_counter, 400);
+    }
+
+    #[test]
+    fn test_instructions_arithmetic() {
+        let mut vm = VirtualMachine::builder().build();
+        execute(&mut vm, Instruction::Add(R0, 32758, 15)).unwrap();
+        assert_eq!(vm.memory.registers[0], 5);
+        assert_eq!(vm.program_counter, 104);
+
+        execute(&mut vm, Instruction::Mult(R0, 32767, 2)).unwrap();
+        assert_eq!(vm.memory.registers[0], 32766);
+        execute(&mut vm, Instruction::Mult(R0, 1000, 1000)).unwrap();
+        assert_eq!(vm.memory.registers[0], (1_000_000 % 32768) as u16);
+
+        execute(&mut vm, Instruction::Mod(R0, 17, 5)).unwrap();
+        assert_eq!(vm.memory.registers[0], 2);
+        assert_eq!(
+            execute(&mut vm, Instruction::Mod(R0, 17, 0)),
+            Err(VmError::DivisionByZero)
+        );
+        assert_eq!(vm.memory.registers[0], 2);
+    }
+
+    #[test]
+    fn test_instructions_bitwise() {
+        let mut vm = VirtualMachine::builder().build();
+        execute(&mut vm, Instruction::And(R0, 0b1100, 0b1010)).unwrap();
+        assert_eq!(vm.memory.registers[0], 0b1000);
+        execute(&mut vm, Instruction::Or(R0, 0b1100, 0b1010)).unwrap();
+        assert_eq!(vm.memory.registers[0], 0b1110);
+
+        execute(&mut vm, Instruction::Not(R0, 0)).unwrap();
+        assert_eq!(vm.memory.registers[0], 32767);
+        assert_eq!(vm.program_counter, 103);
+        execute(&mut vm, Instruction::Not(R0, 0b101)).unwrap();
+        assert_eq!(vm.memory.registers[0], 32767 - 0b101);
+    }
+
+    #[test]
+    fn test_instructions_memory() {
+        let mut vm = VirtualMachine::builder().build();
+        vm.memory.heap[2000] = 77;
+        vm.memory.registers[1] = 2000;
+        execute(&mut vm, Instruction::Load(R0, R1)).unwrap();
+        assert_eq!(vm.memory.registers[0], 77);
+        assert_eq!(vm.program_counter, 103);
+
+        execute(&mut vm, Instruction::Store(2001, R0)).unwrap();
+        assert_eq!(vm.memory.heap[2001], 77);
+        execute(&mut vm, Instruction::Store(R1, 5)).unwrap();
+        assert_eq!(vm.memory.heap[2000], 5);
+    }
+
+    #[test]
+    fn test_instructions_io() {
+        let mut vm = VirtualMachine::builder().build();
+        vm.prefeed_stdin("hi");
+        execute(&mut vm, Instruction::In(R0)).unwrap();
+        assert_eq!(vm.memory.registers[0], 'h' as u16);
+        assert_eq!(vm.program_counter, 102);
+
+        execute(&mut vm, Instruction::Out(R0)).unwrap();
+        execute(&mut vm, Instruction::Out('!' as u16)).unwrap();
+        assert_eq!(vm.output_buffer, "h!");
+
+        assert_eq!(
+            execute(&mut vm, Instruction::In(5)),
+            Err(VmError::WriteToLiteral(5))
+        );
     }
 }
-*/
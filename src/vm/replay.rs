@@ -0,0 +1,354 @@
+use std::fmt::Display;
+
+use super::{
+    execution::{StopReason, INPUT_CYCLE_LIMIT},
+    memory::AMOUNT_REGISTERS,
+    savestate::SavestateError,
+    VirtualMachine,
+};
+
+/// Characters of output shown when an `#expect` fails.
+const DIVERGENCE_OUTPUT_CHARS: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayStep {
+    /// A line typed into the game.
+    Command(String),
+    /// `#checkpoint <name>` saves the state into a slot.
+    Checkpoint(String),
+    /// `#expect "<text>"` requires the text in the output of the previous command.
+    Expect(String),
+    /// `#setr <register> <value>`
+    SetRegister(usize, u16),
+    /// `#break` pauses the machine.
+    Break,
+    /// `#solve <puzzle>` queues the commands solving the puzzle, see [`Solver`].
+    Solve(String),
+}
+
+impl Display for ReplayStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Command(command) => write!(f, "{}", command),
+            Self::Checkpoint(name) => write!(f, "#checkpoint {}", name),
+            Self::Expect(text) => write!(f, "#expect {:?}", text),
+            Self::SetRegister(register, value) => write!(f, "#setr {} {}", register, value),
+            Self::Break => write!(f, "#break"),
+            Self::Solve(puzzle) => write!(f, "#solve {}", puzzle),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// Line number and text of a directive that could not be parsed.
+    InvalidDirective(usize, String),
+    /// The step at the given line did not go as recorded.
+    Diverged(usize, ReplayStep, Divergence),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    /// The expected text is missing, holds the output that was produced instead.
+    MissingOutput(String),
+    /// The machine stopped for another reason than waiting for the next command.
+    Stopped(StopReason),
+    Savestate(String),
+    /// A solver could not find a solution.
+    Solver(String),
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidDirective(line, text) => {
+                write!(f, "line {}: invalid directive '{}'", line, text)
+            }
+            Self::Diverged(line, step, divergence) => {
+                write!(f, "line {} '{}' diverged: ", line, step)?;
+                match divergence {
+                    Divergence::MissingOutput(output) => {
+                        write!(f, "expected text not found in output {:?}", output)
+                    }
+                    Divergence::Stopped(StopReason::Halted) => write!(f, "machine halted"),
+                    Divergence::Stopped(StopReason::Fault(error)) => write!(f, "{}", error),
+                    Divergence::Stopped(StopReason::InputCycleLimit) => {
+                        write!(f, "no input requested within {} cycles", INPUT_CYCLE_LIMIT)
+                    }
+                    Divergence::Stopped(reason) => write!(f, "machine stopped ({:?})", reason),
+                    Divergence::Savestate(error) | Divergence::Solver(error) => {
+                        write!(f, "{}", error)
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Parses the quoted argument of `#expect`, supporting `\n`, `\"` and `\\`.
+fn parse_quoted(text: &str) -> Option<String> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut result = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                'n' => result.push('\n'),
+                escaped @ ('"' | '\\') => result.push(escaped),
+                _ => return None,
+            },
+            '"' => return None,
+            c => result.push(c),
+        }
+    }
+    Some(result)
+}
+
+fn parse_directive(directive: &str, argument: &str) -> Option<ReplayStep> {
+    match directive {
+        "checkpoint" if !argument.is_empty() => Some(ReplayStep::Checkpoint(argument.to_string())),
+        "expect" => parse_quoted(argument).map(ReplayStep::Expect),
+        "setr" => {
+            let (register, value) = argument.split_once(char::is_whitespace)?;
+            let register = register.trim_start_matches('r').parse::<usize>().ok()?;
+            let value = value.trim().parse::<u16>().ok()?;
+            (register < AMOUNT_REGISTERS).then_some(ReplayStep::SetRegister(register, value))
+        }
+        "break" if argument.is_empty() => Some(ReplayStep::Break),
+        "solve" if !argument.is_empty() => Some(ReplayStep::Solve(argument.to_string())),
+        _ => None,
+    }
+}
+
+/// A scripted play-through.
+///
+/// Every line is a command typed into the game, except for empty lines, comments starting
+/// with `# ` and the directives `#checkpoint`, `#expect`, `#setr`, `#break` and `#solve`.
+/// Plain command lists like `history.txt` are valid replays.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Replay {
+    /// Steps with their line number.
+    pub steps: Vec<(usize, ReplayStep)>,
+}
+
+impl Replay {
+    pub fn parse(text: &str) -> Result<Self, ReplayError> {
+        let mut steps = vec![];
+        for (idx, line) in text.lines().enumerate() {
+            let line_number = idx + 1;
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            let Some(rest) = line.strip_prefix('#') else {
+                steps.push((line_number, ReplayStep::Command(line.to_string())));
+                continue;
+            };
+            if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+                continue;
+            }
+            let (directive, argument) = rest.split_once(' ').unwrap_or((rest, ""));
+            let step = parse_directive(directive, argument.trim())
+                .ok_or_else(|| ReplayError::InvalidDirective(line_number, line.to_string()))?;
+            steps.push((line_number, step));
+        }
+        Ok(Self { steps })
+    }
+
+    /// Plain typed input like the history file, every line is a command even when it starts
+    /// with `#`.
+    pub fn from_input(text: &str) -> Self {
+        Self {
+            steps: text
+                .lines()
+                .enumerate()
+                .map(|(idx, line)| (idx + 1, ReplayStep::Command(line.to_string())))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayStatus {
+    Finished,
+    /// Stopped at the `#break` on the given line.
+    Break(usize),
+    /// A breakpoint or watchpoint paused the machine.
+    Paused,
+}
+
+/// Handles `#solve <puzzle>` by queueing the commands that solve the named puzzle.
+///
+/// The machine waits at the prompt when it is called. Puzzles belong to the game, so the caller
+/// of the runner provides the solver.
+pub type Solver = fn(&mut VirtualMachine, &str) -> Result<(), String>;
+
+/// Feeds a replay into a machine step by step, checking it against the produced output.
+#[derive(Debug, Default, Clone)]
+pub struct ReplayRunner {
+    replay: Replay,
+    position: usize,
+    /// Output since the last command was sent.
+    output: String,
+    solver: Option<Solver>,
+}
+
+impl ReplayRunner {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the solver for `#solve`, without one the directive diverges.
+    pub fn with_solver(mut self, solver: Solver) -> Self {
+        self.solver = Some(solver);
+        self
+    }
+
+    /// Line number of the last started step, 0 before the first one.
+    fn current_line(&self) -> usize {
+        self.position
+            .checked_sub(1)
+            .map_or(0, |idx| self.replay.steps[idx].0)
+    }
+
+    fn diverged(&self, divergence: Divergence) -> ReplayError {
+        let (line, step) = match self.position.checked_sub(1) {
+            Some(idx) => self.replay.steps[idx].clone(),
+            None => (0, ReplayStep::Command(String::new())),
+        };
+        ReplayError::Diverged(line, step, divergence)
+    }
+
+    /// Runs the machine through the remaining steps, returning early on a pause.
+    ///
+    /// Calling it again after a pause continues with the next step.
+    pub fn run(&mut self, vm: &mut VirtualMachine) -> Result<ReplayStatus, ReplayError> {
+        loop {
+            let outcome = vm.run_until_input();
+            self.output.push_str(&outcome.output);
+            match outcome.reason {
+                StopReason::AwaitingInput | StopReason::Halted => {}
+                StopReason::Paused => return Ok(ReplayStatus::Paused),
+                reason => return Err(self.diverged(Divergence::Stopped(reason))),
+            }
+
+            loop {
+                let Some((_, step)) = self.replay.steps.get(self.position).cloned() else {
+                    return Ok(ReplayStatus::Finished);
+                };
+                self.position += 1;
+
+                match step {
+                    ReplayStep::Command(_) | ReplayStep::Solve(_) if vm.halted => {
+                        return Err(self.diverged(Divergence::Stopped(StopReason::Halted)));
+                    }
+                    ReplayStep::Command(command) => {
+                        vm.prefeed_stdin(&format!("{}\n", command));
+                        self.output.clear();
+                        break;
+                    }
+                    ReplayStep::Solve(puzzle) => {
+                        let solver = self.solver.ok_or_else(|| {
+                            self.diverged(Divergence::Solver("no solver given".to_string()))
+                        })?;
+                        solver(vm, &puzzle)
+                            .map_err(|error| self.diverged(Divergence::Solver(error)))?;
+                        self.output.clear();
+                        break;
+                    }
+                    ReplayStep::Checkpoint(name) => {
+                        vm.save_state_to_slot(&name)
+                            .map_err(|error: SavestateError| {
+                                self.diverged(Divergence::Savestate(error.to_string()))
+                            })?;
+                    }
+                    ReplayStep::Expect(text) => {
+                        if !self.output.contains(&text) {
+                            let skip = self
+                                .output
+                                .chars()
+                                .count()
+                                .saturating_sub(DIVERGENCE_OUTPUT_CHARS);
+                            let output = self.output.chars().skip(skip).collect();
+                            return Err(self.diverged(Divergence::MissingOutput(output)));
+                        }
+                    }
+                    ReplayStep::SetRegister(register, value) => {
+                        vm.memory.registers[register] = value;
+                    }
+                    ReplayStep::Break => {
+                        vm.paused = true;
+                        return Ok(ReplayStatus::Break(self.current_line()));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{transform_bytes_to_program_code, vm::VirtualMachine};
+
+    use super::{Divergence, Replay, ReplayError, ReplayRunner, ReplayStatus, ReplayStep};
+
+    #[test]
+    fn test_parse_replay() {
+        let replay = Replay::parse(
+            "# comment\ntake tablet\n\n#expect \"Taken.\\n\"\n#setr r7 25734\n#break\n",
+        )
+        .unwrap();
+        assert_eq!(
+            replay.steps,
+            vec![
+                (2, ReplayStep::Command("take tablet".to_string())),
+                (4, ReplayStep::Expect("Taken.\n".to_string())),
+                (5, ReplayStep::SetRegister(7, 25734)),
+                (6, ReplayStep::Break),
+            ]
+        );
+        assert_eq!(
+            Replay::parse("#solve coins").unwrap().steps,
+            vec![(1, ReplayStep::Solve("coins".to_string()))]
+        );
+        assert_eq!(
+            Replay::parse("look\n#setr 8 1"),
+            Err(ReplayError::InvalidDirective(2, "#setr 8 1".to_string()))
+        );
+
+        assert_eq!(
+            Replay::from_input("look\n#foo\n").steps,
+            vec![
+                (1, ReplayStep::Command("look".to_string())),
+                (2, ReplayStep::Command("#foo".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_replay() {
+        let program =
+            transform_bytes_to_program_code(include_bytes!("../../payloads/challenge.bin"))
+                .unwrap();
+        let mut vm = VirtualMachine::builder().program(&program).build();
+        let replay = Replay::parse(
+            "take tablet\n#expect \"Taken.\"\n#break\nuse tablet\n#expect \"Taken.\"\n",
+        )
+        .unwrap();
+        let mut runner = ReplayRunner::new(replay);
+
+        assert_eq!(runner.run(&mut vm), Ok(ReplayStatus::Break(3)));
+        assert!(vm.paused);
+        assert_eq!(runner.run(&mut vm), Ok(ReplayStatus::Paused));
+        vm.resume();
+        match runner.run(&mut vm) {
+            Err(ReplayError::Diverged(5, ReplayStep::Expect(_), Divergence::MissingOutput(_))) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::VecDeque;
+
+use super::{
+    codes::CodeDetector,
+    devices::BufferOutput,
+    memory::{AMOUNT_REGISTERS, MAX_ADDRESS},
+    opcodes::Instruction,
+    VirtualMachine, VirtualMachineSavestate,
+};
+
+/// Amount of per-cycle undo deltas kept in the ring buffer.
+pub const REWIND_CAPACITY: usize = 200_000;
+/// Amount of cycles between two full checkpoints.
+pub const CHECKPOINT_INTERVAL: usize = 100_000;
+/// Amount of full checkpoints kept.
+pub const CHECKPOINT_CAPACITY: usize = 16;
+
+/// Everything needed to undo a single cycle.
+#[derive(Debug, Clone)]
+pub struct CycleDelta {
+    pub program_counter: u16,
+    pub paused: bool,
+    pub halted: bool,
+    pub registers: [u16; AMOUNT_REGISTERS],
+    /// Address and previous value of a heap write done by `WMEM`.
+    pub heap_write: Option<(u16, u16)>,
+    pub stack_length: usize,
+    pub stack_top: Option<(u16, Option<u16>)>,
+    pub output_length: usize,
+    pub consumed_input: Option<u8>,
+    /// Unfinished word of the code detector before an `OUT` fed it a character.
+    pub code_word: Option<String>,
+    /// Code the `OUT` completed.
+    pub found_code: Option<usize>,
+}
+
+/// Full state to re-execute from, the code detector is not part of a savestate.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub state: VirtualMachineSavestate,
+    pub codes: CodeDetector,
+}
+
+#[derive(Debug, Default)]
+pub struct Rewind {
+    /// Off by default, the recording costs time and memory on every cycle.
+    pub enabled: bool,
+    pub deltas: VecDeque<CycleDelta>,
+    pub checkpoints: VecDeque<Checkpoint>,
+}
+
+impl Rewind {
+    pub fn clear(&mut self) {
+        self.deltas.clear();
+        self.checkpoints.clear();
+    }
+}
+
+// Rewind
+impl VirtualMachine {
+    pub fn record_delta(&self, instruction: &Instruction) -> CycleDelta {
+        let heap_write = match instruction {
+            Instruction::Store(address, _) => {
+                let target = self.memory.resolve(address).unwrap_or(u16::MAX);
+                (target <= MAX_ADDRESS).then(|| (target, self.memory.heap[target as usize]))
+            }
+            _ => None,
+        };
+
+        CycleDelta {
+            program_counter: self.program_counter,
+            paused: self.paused,
+            halted: self.halted,
+            registers: self.memory.registers,
+            heap_write,
+            stack_length: self.memory.stack.len(),
+            stack_top: self.memory.stack.last().copied(),
+            output_length: self.output_buffer.len(),
+            consumed_input: None,
+            code_word: matches!(instruction, Instruction::Out(_))
+                .then(|| self.codes.word().to_string()),
+            found_code: None,
+        }
+    }
+
+    pub fn finish_delta(&mut self, mut delta: CycleDelta, instruction: &Instruction) {
+        match instruction {
+            Instruction::In(address) => {
+                delta.consumed_input = self.memory.resolve(address).ok().map(|value| value as u8);
+            }
+            Instruction::Out(_) => delta.found_code = self.codes.last_found(),
+            _ => {}
+        }
+
+        if self.rewind.deltas.len() >= REWIND_CAPACITY {
+            self.rewind.deltas.pop_front();
+        }
+        self.rewind.deltas.push_back(delta);
+    }
+
+    pub fn record_checkpoint(&mut self) {
+        if !self.rewind.enabled {
+            return;
+        }
+        let due =
+            self.cycle.is_multiple_of(CHECKPOINT_INTERVAL) || self.rewind.checkpoints.is_empty();
+        let taken = self
+            .rewind
+            .checkpoints
+            .back()
+            .is_some_and(|checkpoint| checkpoint.state.cycle == self.cycle);
+        if !due || taken {
+            return;
+        }
+        if self.rewind.checkpoints.len() >= CHECKPOINT_CAPACITY {
+            self.rewind.checkpoints.pop_front();
+        }
+        let checkpoint = Checkpoint {
+            state: self.get_state(),
+            codes: self.codes.clone(),
+        };
+        self.rewind.checkpoints.push_back(checkpoint);
+    }
+
+    fn undo_delta(&mut self, delta: CycleDelta) {
+        self.breakpoints
+            .uncheck(self.program_counter, &self.memory.registers);
+        self.program_counter = delta.program_counter;
+        self.paused = delta.paused;
+        self.halted = delta.halted;
+        self.awaiting_input = false;
+        self.memory.registers = delta.registers;
+        if let Some((address, value)) = delta.heap_write {
+            self.memory.heap[address as usize] = value;
+        }
+        if self.memory.stack.len() >= delta.stack_length {
+            self.memory.stack.truncate(delta.stack_length);
+        } else if let Some(top) = delta.stack_top {
+            self.memory.stack.push(top);
+        }
+        self.output_buffer.truncate(delta.output_length);
+        if let Some(character) = delta.consumed_input {
+            self.stdin_buffer.push_front(character);
+            self.stdin_history.pop();
+        }
+        if let Some(word) = delta.code_word {
+            self.codes.undo_push(word, delta.found_code);
+        }
+        self.cycle -= 1;
+    }
+
+    /// Re-executes from the latest checkpoint before `target_cycle` to refill the deltas.
+    fn rebuild_from_checkpoint(&mut self, target_cycle: usize) -> bool {
+        while self
+            .rewind
+            .checkpoints
+            .back()
+            .is_some_and(|checkpoint| checkpoint.state.cycle > target_cycle)
+        {
+            self.rewind.checkpoints.pop_back();
+        }
+        let Some(Checkpoint {
+            state: checkpoint,
+            codes,
+        }) = self.rewind.checkpoints.back().cloned()
+        else {
+            return false;
+        };
+
+        // input consumed after the checkpoint was taken has to be available again
+        let mut stdin_buffer: VecDeque<u8> = self
+            .stdin_history
+            .chars()
+            .skip(checkpoint.stdin_history.chars().count())
+            .map(|c| c as u8)
+            .collect();
+        stdin_buffer.extend(self.stdin_buffer.drain(..));
+
+        // the hits of the undone cycle are taken back, the re-executed ones are still counted
+        self.breakpoints
+            .uncheck(self.program_counter, &self.memory.registers);
+        let watchpoints = std::mem::take(&mut self.memory.watchpoints.entries);
+        let trace = self.trace.take();
+        let transcript = self.transcript.take();
+        let subscriber = self.subscriber.take();
+        let output = std::mem::replace(&mut self.output, Box::new(BufferOutput::default()));
+        self.halted = checkpoint.halted;
+        self.awaiting_input = false;
+        self.cycle = checkpoint.cycle;
+        self.program_counter = checkpoint.program_counter;
+        self.memory = checkpoint.memory;
+        self.memory.watchpoints = Default::default();
+        self.stdin_history = checkpoint.stdin_history;
+        self.stdin_buffer = stdin_buffer;
+        self.output_buffer = checkpoint.output_buffer;
+        self.codes = codes;
+        self.rewind.deltas.clear();
+
+        while self.cycle < target_cycle && !self.halted {
+            if self.step().is_err() {
+                break;
+            }
+        }
+        self.memory.watchpoints.entries = watchpoints;
+        self.memory.watchpoints.hits.clear();
+        self.trace = trace;
+        self.transcript = transcript;
+        self.subscriber = subscriber;
+        self.output = output;
+        true
+    }
+
+    /// Undoes the last executed cycle, returns false if there is no history left.
+    pub fn step_back(&mut self) -> bool {
+        match self.rewind.deltas.pop_back() {
+            Some(delta) => {
+                self.undo_delta(delta);
+                true
+            }
+            None => match self.cycle.checked_sub(1) {
+                Some(target_cycle) => self.rebuild_from_checkpoint(target_cycle),
+                None => false,
+            },
+        }
+    }
+
+    /// Steps back until an enabled breakpoint matches or the history is exhausted.
+    pub fn reverse_continue(&mut self) -> Option<usize> {
+        while self.step_back() {
+            if let Some(id) = self
+                .breakpoints
+                .find_match(self.program_counter, &self.memory.registers)
+            {
+                return Some(id);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        transform_bytes_to_program_code,
+        vm::{VirtualMachine, VirtualMachineSavestate},
+    };
+
+    fn challenge_vm() -> VirtualMachine {
+        let program =
+            transform_bytes_to_program_code(include_bytes!("../../payloads/challenge.bin"))
+                .unwrap();
+        VirtualMachine::builder()
+            .program(&program)
+            .rewind(true)
+            .build()
+    }
+
+    fn assert_state_eq(vm: &mut VirtualMachine, expected: &VirtualMachineSavestate) {
+        let state = vm.get_state();
+        assert_eq!(state.cycle, expected.cycle);
+        assert_eq!(state.program_counter, expected.program_counter);
+        assert_eq!(state.paused, expected.paused);
+        assert_eq!(state.halted, expected.halted);
+        assert_eq!(state.memory.heap, expected.memory.heap);
+        assert_eq!(state.memory.registers, expected.memory.registers);
+        assert_eq!(state.memory.stack, expected.memory.stack);
+        assert_eq!(state.output_buffer, expected.output_buffer);
+        assert_eq!(state.stdin_history, expected.stdin_history);
+        assert_eq!(state.stdin_buffer, expected.stdin_buffer);
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut vm = challenge_vm();
+        vm.run_for(100);
+        let snapshot = vm.get_state();
+
+        let cycles = vm.run_for(50_000).cycles;
+        assert_eq!(cycles, 50_000);
+        assert_ne!(vm.output_buffer, snapshot.output_buffer);
+        for _ in 0..cycles {
+            assert!(vm.step_back());
+        }
+        assert_state_eq(&mut vm, &snapshot);
+    }
+
+    #[test]
+    fn test_step_back_from_checkpoint() {
+        let mut vm = challenge_vm();
+        vm.run_for(100);
+        let snapshot = vm.get_state();
+
+        let cycles = vm.run_for(30_000).cycles;
+        // without deltas the history is re-executed from the checkpoint of cycle 0
+        vm.rewind.deltas.clear();
+        assert!(vm.step_back());
+        assert_eq!(vm.cycle, snapshot.cycle + cycles - 1);
+        assert!(!vm.rewind.deltas.is_empty());
+        for _ in 1..cycles {
+            assert!(vm.step_back());
+        }
+        assert_state_eq(&mut vm, &snapshot);
+    }
+
+    #[test]
+    fn test_reverse_continue() {
+        // OUT for every character of a code, then HALT
+        let mut program: Vec<u16> = "ImoFztWQCvxj\n"
+            .chars()
+            .flat_map(|c| [19, c as u16])
+            .collect();
+        program.push(0);
+
+        let mut vm = VirtualMachine::builder()
+            .program(&program)
+            .rewind(true)
+            .build();
+        let start = vm.get_state();
+        let id = vm.breakpoints.add(10, None, None);
+        while !vm.halted {
+            vm.cycle().unwrap();
+        }
+        assert!(vm.paused);
+        assert_eq!(vm.codes.found(), 1);
+
+        assert_eq!(vm.reverse_continue(), Some(id));
+        assert_eq!(vm.program_counter, 10);
+        assert_eq!(vm.output_buffer, "ImoFz");
+        assert!(vm.paused);
+        assert_eq!(vm.codes.found(), 0);
+
+        // stepping forward again finds the code a second time
+        vm.paused = false;
+        while !vm.halted {
+            vm.cycle().unwrap();
+        }
+        assert_eq!(vm.codes.found(), 1);
+
+        while vm.step_back() {}
+        assert_eq!(vm.codes.found(), 0);
+        assert_state_eq(&mut vm, &start);
+    }
+
+    #[test]
+    fn test_step_back_over_input() {
+        // IN and OUT of two characters, then HALT
+        let program = [20, 32768, 19, 32768, 20, 32768, 19, 32768, 0];
+        let mut vm = VirtualMachine::builder()
+            .program(&program)
+            .rewind(true)
+            .build();
+        vm.prefeed_stdin("ab");
+        let start = vm.get_state();
+        vm.breakpoints.add(2, None, None);
+        while !vm.halted {
+            vm.cycle().unwrap();
+        }
+        assert_eq!(vm.stdin_history, "ab");
+        assert_eq!(vm.breakpoints.entries[0].hits, 1);
+
+        for _ in 0..3 {
+            assert!(vm.step_back());
+        }
+        assert_eq!(vm.stdin_history, "a");
+        assert_eq!(vm.stdin_buffer, b"b".to_vec());
+
+        // the rebuild consumes the input up to the target again
+        vm.rewind.deltas.clear();
+        assert!(vm.step_back());
+        assert_eq!(vm.cycle, 1);
+        assert_eq!(vm.stdin_history, "a");
+        assert_eq!(vm.stdin_buffer, b"b".to_vec());
+        assert_eq!(vm.breakpoints.entries[0].hits, 1);
+
+        assert!(vm.step_back());
+        assert_eq!(vm.breakpoints.entries[0].hits, 0);
+        // the breakpoint paused the machine
+        vm.resume();
+        assert_state_eq(&mut vm, &start);
+    }
+}
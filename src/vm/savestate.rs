@@ -0,0 +1,493 @@
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    breakpoints::{Breakpoint, Breakpoints, Comparison, Condition},
+    memory::{Memory, AMOUNT_REGISTERS, HEAP_SIZE},
+    VirtualMachineSavestate,
+};
+
+pub const SAVESTATE_DIRECTORY: &str = "./savestates";
+pub const SAVESTATE_EXTENSION: &str = "sav";
+pub const SAVESTATE_MAGIC: &[u8; 8] = b"SYNACSAV";
+/// Version 2 added the breakpoint table.
+pub const SAVESTATE_VERSION: u16 = 2;
+pub const QUICKSAVE_SLOT: &str = "quicksave";
+pub const SUMMARY_OUTPUT_LINES: usize = 6;
+
+const HEADER_LENGTH: usize = SAVESTATE_MAGIC.len() + 2 + 4 + 4;
+
+#[derive(Debug)]
+pub enum SavestateError {
+    Io(io::Error),
+    InvalidSlotName(String),
+    /// A rename would overwrite the slot with this name.
+    SlotExists(String),
+    BadMagic,
+    UnsupportedVersion(u16),
+    ChecksumMismatch,
+    Truncated,
+    InvalidText,
+    InvalidBreakpoint,
+}
+
+impl Display for SavestateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::InvalidSlotName(name) => write!(f, "invalid slot name '{}'", name),
+            Self::SlotExists(name) => write!(f, "slot '{}' already exists", name),
+            Self::BadMagic => write!(f, "not a savestate file"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported savestate version {}", version)
+            }
+            Self::ChecksumMismatch => write!(f, "savestate checksum mismatch"),
+            Self::Truncated => write!(f, "savestate is truncated"),
+            Self::InvalidText => write!(f, "savestate contains invalid text"),
+            Self::InvalidBreakpoint => write!(f, "savestate contains an invalid breakpoint"),
+        }
+    }
+}
+
+impl From<io::Error> for SavestateError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// FNV-1a, good enough to detect truncated or corrupted files.
+fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+/// Returns the file path of a named savestate slot in the directory.
+pub fn slot_path(directory: &Path, name: &str) -> Result<PathBuf, SavestateError> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(SavestateError::InvalidSlotName(name.to_string()));
+    }
+
+    let mut path = directory.join(name);
+    path.set_extension(SAVESTATE_EXTENSION);
+    Ok(path)
+}
+
+/// Returns the names of all slots stored in the directory.
+pub fn list_slots(directory: &Path) -> Result<Vec<String>, SavestateError> {
+    let mut names = vec![];
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(names),
+        Err(error) => return Err(error.into()),
+    };
+
+    for entry in entries {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|ext| ext == SAVESTATE_EXTENSION)
+        {
+            if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+pub fn rename_slot(directory: &Path, name: &str, new_name: &str) -> Result<(), SavestateError> {
+    let path = slot_path(directory, name)?;
+    let new_path = slot_path(directory, new_name)?;
+    if new_path.exists() {
+        return Err(SavestateError::SlotExists(new_name.to_string()));
+    }
+    fs::rename(path, new_path)?;
+    Ok(())
+}
+
+pub fn delete_slot(directory: &Path, name: &str) -> Result<(), SavestateError> {
+    match fs::remove_file(slot_path(directory, name)?) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Short description of a slot for the slot browser.
+#[derive(Debug, Clone)]
+pub struct VirtualMachineSavestateSummary {
+    pub name: String,
+    pub cycle: usize,
+    pub program_counter: u16,
+    pub output_tail: Vec<String>,
+}
+
+#[derive(Default)]
+struct SavestateWriter {
+    data: Vec<u8>,
+}
+
+impl SavestateWriter {
+    fn put_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    fn put_u16(&mut self, value: u16) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    fn put_u32(&mut self, value: u32) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    fn put_u64(&mut self, value: u64) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    fn put_bytes(&mut self, value: &[u8]) {
+        self.put_u32(value.len() as u32);
+        self.data.extend(value);
+    }
+}
+
+struct SavestateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> SavestateReader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], SavestateError> {
+        let end = self.position + length;
+        let slice = self
+            .data
+            .get(self.position..end)
+            .ok_or(SavestateError::Truncated)?;
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn get_u8(&mut self) -> Result<u8, SavestateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn get_u16(&mut self) -> Result<u16, SavestateError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn get_u32(&mut self) -> Result<u32, SavestateError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn get_u64(&mut self) -> Result<u64, SavestateError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn get_bytes(&mut self) -> Result<&'a [u8], SavestateError> {
+        let length = self.get_u32()? as usize;
+        self.take(length)
+    }
+
+    fn get_string(&mut self) -> Result<String, SavestateError> {
+        String::from_utf8(self.get_bytes()?.to_vec()).map_err(|_| SavestateError::InvalidText)
+    }
+}
+
+impl VirtualMachineSavestate {
+    pub fn summary(&self, name: &str) -> VirtualMachineSavestateSummary {
+        let lines: Vec<&str> = self
+            .output_buffer
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        VirtualMachineSavestateSummary {
+            name: name.to_string(),
+            cycle: self.cycle,
+            program_counter: self.program_counter,
+            output_tail: lines[lines.len().saturating_sub(SUMMARY_OUTPUT_LINES)..]
+                .iter()
+                .map(|line| line.to_string())
+                .collect(),
+        }
+    }
+
+    /// Serializes the savestate into the versioned binary format.
+    ///
+    /// Layout: magic, version (u16), payload length (u32), payload checksum (u32), payload.
+    /// All numbers are little-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut payload = SavestateWriter::default();
+        payload.put_u8(self.paused as u8 | (self.halted as u8) << 1);
+        payload.put_u64(self.cycle as u64);
+        payload.put_u16(self.program_counter);
+        for value in self.memory.heap.iter() {
+            payload.put_u16(*value);
+        }
+        for value in self.memory.registers.iter() {
+            payload.put_u16(*value);
+        }
+        payload.put_u32(self.memory.stack.len() as u32);
+        for (value, call) in self.memory.stack.iter() {
+            payload.put_u16(*value);
+            payload.put_u8(call.is_some() as u8);
+            payload.put_u16(call.unwrap_or_default());
+        }
+        payload.put_bytes(self.stdin_history.as_bytes());
+        payload.put_bytes(&self.stdin_buffer.iter().copied().collect::<Vec<u8>>());
+        payload.put_bytes(self.output_buffer.as_bytes());
+
+        payload.put_u32(self.breakpoints.entries.len() as u32);
+        for breakpoint in self.breakpoints.entries.iter() {
+            payload.put_u32(breakpoint.id as u32);
+            payload.put_u16(breakpoint.address);
+            payload.put_u8(breakpoint.enabled as u8);
+            payload.put_u32(breakpoint.hit_count.map_or(0, |count| count as u32 + 1));
+            payload.put_u32(breakpoint.hits as u32);
+            payload.put_u8(breakpoint.condition.is_some() as u8);
+            let condition = breakpoint.condition.unwrap_or(Condition {
+                register: 0,
+                comparison: Comparison::Equal,
+                value: 0,
+            });
+            payload.put_u8(condition.register as u8);
+            payload.put_u8(condition.comparison.to_u8());
+            payload.put_u16(condition.value);
+        }
+
+        let mut result = SavestateWriter::default();
+        result.data.extend(SAVESTATE_MAGIC);
+        result.put_u16(SAVESTATE_VERSION);
+        result.put_u32(payload.data.len() as u32);
+        result.put_u32(checksum(&payload.data));
+        result.data.extend(payload.data);
+        result.data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SavestateError> {
+        if data.len() < HEADER_LENGTH {
+            return Err(SavestateError::Truncated);
+        }
+        let mut header = SavestateReader { data, position: 0 };
+        if header.take(SAVESTATE_MAGIC.len())? != SAVESTATE_MAGIC {
+            return Err(SavestateError::BadMagic);
+        }
+        let version = header.get_u16()?;
+        if !(1..=SAVESTATE_VERSION).contains(&version) {
+            return Err(SavestateError::UnsupportedVersion(version));
+        }
+        let length = header.get_u32()? as usize;
+        let expected_checksum = header.get_u32()?;
+        let payload = header.take(length)?;
+        if checksum(payload) != expected_checksum {
+            return Err(SavestateError::ChecksumMismatch);
+        }
+
+        let mut reader = SavestateReader {
+            data: payload,
+            position: 0,
+        };
+        let flags = reader.get_u8()?;
+        let cycle = reader.get_u64()? as usize;
+        let program_counter = reader.get_u16()?;
+
+        let mut memory = Memory::default();
+        for idx in 0..HEAP_SIZE {
+            memory.heap[idx] = reader.get_u16()?;
+        }
+        for idx in 0..AMOUNT_REGISTERS {
+            memory.registers[idx] = reader.get_u16()?;
+        }
+        let stack_length = reader.get_u32()?;
+        for _ in 0..stack_length {
+            let value = reader.get_u16()?;
+            let has_call = reader.get_u8()? != 0;
+            let call = reader.get_u16()?;
+            memory.stack.push((value, has_call.then_some(call)));
+        }
+
+        let stdin_history = reader.get_string()?;
+        let stdin_buffer = reader.get_bytes()?.iter().copied().collect();
+        let output_buffer = reader.get_string()?;
+
+        let mut breakpoints = Breakpoints::default();
+        if version >= 2 {
+            let amount = reader.get_u32()?;
+            for _ in 0..amount {
+                let id = reader.get_u32()? as usize;
+                let address = reader.get_u16()?;
+                let enabled = reader.get_u8()? != 0;
+                let hit_count = reader.get_u32()?;
+                let hits = reader.get_u32()? as usize;
+                let has_condition = reader.get_u8()? != 0;
+                let register = reader.get_u8()? as usize;
+                let comparison = Comparison::from_u8(reader.get_u8()?);
+                let value = reader.get_u16()?;
+
+                let condition = match (has_condition, comparison) {
+                    (false, _) => None,
+                    (true, Some(comparison)) if register < AMOUNT_REGISTERS => Some(Condition {
+                        register,
+                        comparison,
+                        value,
+                    }),
+                    (true, _) => return Err(SavestateError::InvalidBreakpoint),
+                };
+                breakpoints.entries.push(Breakpoint {
+                    id,
+                    address,
+                    enabled,
+                    hit_count: hit_count.checked_sub(1).map(|count| count as usize),
+                    hits,
+                    condition,
+                });
+            }
+        }
+
+        Ok(Self {
+            paused: flags & 1 != 0,
+            halted: flags & 2 != 0,
+            cycle,
+            program_counter,
+            memory,
+            breakpoints,
+            stdin_history,
+            stdin_buffer,
+            output_buffer,
+        })
+    }
+
+    pub fn write_to_slot(&self, directory: &Path, name: &str) -> Result<(), SavestateError> {
+        let path = slot_path(directory, name)?;
+        fs::create_dir_all(directory)?;
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn read_from_slot(directory: &Path, name: &str) -> Result<Self, SavestateError> {
+        Self::from_bytes(&fs::read(slot_path(directory, name)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, env, fs, io, process};
+
+    use crate::vm::{
+        breakpoints::{Breakpoints, Condition},
+        memory::Memory,
+        VirtualMachineSavestate,
+    };
+
+    use super::{
+        delete_slot, list_slots, rename_slot, SavestateError, SAVESTATE_MAGIC, SAVESTATE_VERSION,
+    };
+
+    fn savestate() -> VirtualMachineSavestate {
+        let mut memory = Memory::default();
+        memory.heap[0] = 21;
+        memory.heap[32767] = 19;
+        memory.registers[7] = 25734;
+        memory.stack = vec![(5, None), (1234, Some(6027))];
+        let mut breakpoints = Breakpoints::default();
+        breakpoints.add(6027, Some(2), Condition::parse("r7!=0"));
+        breakpoints.add(843, None, None);
+
+        VirtualMachineSavestate {
+            paused: true,
+            halted: false,
+            cycle: 1_234_567,
+            program_counter: 843,
+            memory,
+            breakpoints,
+            stdin_history: "take tablet\n".to_string(),
+            stdin_buffer: VecDeque::from(b"use tablet\n".to_vec()),
+            output_buffer: "== Foothills ==\n".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_savestate_round_trip() {
+        let state = savestate();
+        let loaded = VirtualMachineSavestate::from_bytes(&state.to_bytes()).unwrap();
+
+        assert_eq!(loaded.paused, state.paused);
+        assert_eq!(loaded.halted, state.halted);
+        assert_eq!(loaded.cycle, state.cycle);
+        assert_eq!(loaded.program_counter, state.program_counter);
+        assert_eq!(loaded.memory.heap, state.memory.heap);
+        assert_eq!(loaded.memory.registers, state.memory.registers);
+        assert_eq!(loaded.memory.stack, state.memory.stack);
+        assert_eq!(loaded.breakpoints, state.breakpoints);
+        assert_eq!(loaded.stdin_history, state.stdin_history);
+        assert_eq!(loaded.stdin_buffer, state.stdin_buffer);
+        assert_eq!(loaded.output_buffer, state.output_buffer);
+    }
+
+    #[test]
+    fn test_savestate_rejects_corruption() {
+        let data = savestate().to_bytes();
+
+        let mut flipped = data.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            VirtualMachineSavestate::from_bytes(&flipped),
+            Err(SavestateError::ChecksumMismatch)
+        ));
+
+        let mut wrong_magic = data.clone();
+        wrong_magic[0] = b'X';
+        assert!(matches!(
+            VirtualMachineSavestate::from_bytes(&wrong_magic),
+            Err(SavestateError::BadMagic)
+        ));
+
+        let mut wrong_version = data.clone();
+        let version = SAVESTATE_VERSION + 1;
+        wrong_version[SAVESTATE_MAGIC.len()..SAVESTATE_MAGIC.len() + 2]
+            .copy_from_slice(&version.to_le_bytes());
+        assert!(matches!(
+            VirtualMachineSavestate::from_bytes(&wrong_version),
+            Err(SavestateError::UnsupportedVersion(v)) if v == version
+        ));
+
+        assert!(matches!(
+            VirtualMachineSavestate::from_bytes(&data[..data.len() - 1]),
+            Err(SavestateError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn test_slot_files() {
+        let directory = env::temp_dir().join(format!("synacor-savestates-{}", process::id()));
+        let state = savestate();
+        state.write_to_slot(&directory, "beach").unwrap();
+        state.write_to_slot(&directory, "vault").unwrap();
+        assert_eq!(list_slots(&directory).unwrap(), vec!["beach", "vault"]);
+
+        let renamed_onto_existing = rename_slot(&directory, "beach", "vault");
+        let renamed_missing = rename_slot(&directory, "orb", "mirror");
+        rename_slot(&directory, "beach", "orb").unwrap();
+        delete_slot(&directory, "vault").unwrap();
+        let names = list_slots(&directory).unwrap();
+        let loaded = VirtualMachineSavestate::read_from_slot(&directory, "orb").unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert!(matches!(
+            renamed_onto_existing,
+            Err(SavestateError::SlotExists(name)) if name == "vault"
+        ));
+        assert!(matches!(
+            renamed_missing,
+            Err(SavestateError::Io(error)) if error.kind() == io::ErrorKind::NotFound
+        ));
+        assert_eq!(names, vec!["orb"]);
+        assert_eq!(loaded.cycle, state.cycle);
+    }
+}
@@ -1,6 +1,10 @@
 use std::sync::mpsc;
 
-use super::{opcodes::Instruction, VirtualMachineSavestate};
+use super::{
+    breakpoints::BreakpointCommand, codes::ExpectedCode, error::VmError, opcodes::Instruction,
+    replay::ReplayRunner, savestate::VirtualMachineSavestateSummary,
+    watchpoints::WatchpointCommand, VirtualMachineSavestate,
+};
 
 #[derive(Debug)]
 pub struct VirtualMachineSubscriber {
@@ -38,29 +42,60 @@ impl VirtualMachineSubscription {
     }
 }
 
+/// Heap cells or registers to overwrite without triggering watchpoints.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MemoryPatch {
+    /// Addresses with their new values.
+    pub writes: Vec<(u16, u16)>,
+    /// Status message once the patch is applied.
+    pub description: String,
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct VirtualMachineSubscriptionTick {
     pub additional_stdin: String,
-    pub save_state: bool,
-    pub load_state: bool,
+    pub save_state_slot: Option<String>,
+    pub load_state_slot: Option<String>,
+    pub rename_state_slot: Option<(String, String)>,
+    pub delete_state_slot: Option<String>,
     pub write_history: bool,
     pub toggle_pause: bool,
     pub step_once: bool,
+    pub step_back: bool,
+    pub reverse_continue: bool,
     pub set_register_id: Option<usize>,
     pub set_register_value: u16,
+    pub breakpoint_command: Option<BreakpointCommand>,
+    pub watchpoint_command: Option<WatchpointCommand>,
+    pub trace_start: Option<String>,
+    pub trace_stop: bool,
+    /// Patches a heap cell or register, e.g. to get past a fault.
+    pub poke: Option<(u16, u16)>,
+    /// Several pokes applied at once, e.g. a fix computed by a solver of the viewer.
+    pub patch: Option<MemoryPatch>,
+    /// Starts a replay unless one is running, e.g. one solving a puzzle.
+    pub replay: Option<ReplayRunner>,
 }
 
 #[derive(Debug)]
 pub struct VirtualMachineSubscriptionUpdate {
-    pub current_instruction: Instruction,
+    pub current_instruction: Result<Instruction, VmError>,
+    pub fault: Option<VmError>,
     pub savestate: VirtualMachineSavestate,
+    pub savestate_slots: Vec<VirtualMachineSavestateSummary>,
+    pub status_message: Option<String>,
+    pub codes: Vec<ExpectedCode>,
 }
 
 impl Default for VirtualMachineSubscriptionUpdate {
     fn default() -> Self {
         Self {
-            current_instruction: Instruction::Noop,
+            current_instruction: Ok(Instruction::Noop),
+            fault: None,
             savestate: VirtualMachineSavestate::default(),
+            savestate_slots: vec![],
+            status_message: None,
+            codes: vec![],
         }
     }
 }
@@ -0,0 +1,296 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use super::{
+    memory::{MAX_ADDRESS, REGISTER_ADDRESS_END, REGISTER_ADDRESS_START},
+    opcodes::{format_operand, Instruction},
+    VirtualMachine,
+};
+
+pub const TRACE_MAGIC: &[u8; 8] = b"SYNTRACE";
+pub const TRACE_VERSION: u16 = 1;
+
+/// One executed cycle.
+///
+/// Encoded as: PC (u16), word count (u8), instruction words, one resolved value per
+/// operand, write count (u8) and the `(address, new value)` pairs of all writes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub program_counter: u16,
+    pub words: Vec<u16>,
+    pub operand_values: Vec<u16>,
+    pub writes: Vec<(u16, u16)>,
+}
+
+impl TraceRecord {
+    pub fn render(&self, cycle: u64) -> String {
+        let instruction = match Instruction::try_from(&self.words[..]) {
+            Ok(instruction) => instruction.to_assembly(),
+            Err(error) => format!("{:?}", error),
+        };
+        let values: Vec<String> = self
+            .operand_values
+            .iter()
+            .map(|value| value.to_string())
+            .collect();
+        let writes: Vec<String> = self
+            .writes
+            .iter()
+            .map(|(address, value)| format!("{}={}", format_operand(*address), value))
+            .collect();
+
+        format!(
+            "{:10} {:5}: {:24} [{}] {}",
+            cycle,
+            self.program_counter,
+            instruction,
+            values.join(" "),
+            writes.join(" ")
+        )
+        .trim_end()
+        .to_string()
+    }
+}
+
+#[derive(Debug)]
+pub struct TraceWriter {
+    writer: BufWriter<File>,
+}
+
+impl TraceWriter {
+    pub fn create(path: &Path, start_cycle: u64) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(TRACE_MAGIC)?;
+        writer.write_all(&TRACE_VERSION.to_le_bytes())?;
+        writer.write_all(&start_cycle.to_le_bytes())?;
+        Ok(Self { writer })
+    }
+
+    pub fn write(&mut self, record: &TraceRecord) -> io::Result<()> {
+        let mut data = Vec::with_capacity(
+            4 + 2 * (record.words.len() + record.operand_values.len() + 2 * record.writes.len()),
+        );
+        data.extend(record.program_counter.to_le_bytes());
+        data.push(record.words.len() as u8);
+        for value in record.words.iter().chain(record.operand_values.iter()) {
+            data.extend(value.to_le_bytes());
+        }
+        data.push(record.writes.len() as u8);
+        for (address, value) in record.writes.iter() {
+            data.extend(address.to_le_bytes());
+            data.extend(value.to_le_bytes());
+        }
+        self.writer.write_all(&data)
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+pub struct TraceReader<R: Read> {
+    reader: R,
+    pub cycle: u64,
+}
+
+impl TraceReader<BufReader<File>> {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> TraceReader<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0u8; 18];
+        reader.read_exact(&mut header)?;
+        if &header[0..8] != TRACE_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a trace file",
+            ));
+        }
+        let version = u16::from_le_bytes([header[8], header[9]]);
+        if version != TRACE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported trace version {}", version),
+            ));
+        }
+        let cycle = u64::from_le_bytes(header[10..18].try_into().unwrap());
+        Ok(Self { reader, cycle })
+    }
+
+    fn read_u8(&mut self) -> io::Result<u8> {
+        let mut buffer = [0u8; 1];
+        self.reader.read_exact(&mut buffer)?;
+        Ok(buffer[0])
+    }
+
+    fn read_u16(&mut self) -> io::Result<u16> {
+        let mut buffer = [0u8; 2];
+        self.reader.read_exact(&mut buffer)?;
+        Ok(u16::from_le_bytes(buffer))
+    }
+
+    /// Reads the next record together with its cycle, `None` at the end of the trace.
+    pub fn next_record(&mut self) -> io::Result<Option<(u64, TraceRecord)>> {
+        let mut buffer = [0u8; 2];
+        match self.reader.read(&mut buffer[..1])? {
+            0 => return Ok(None),
+            _ => self.reader.read_exact(&mut buffer[1..])?,
+        }
+        let program_counter = u16::from_le_bytes(buffer);
+
+        let word_count = self.read_u8()? as usize;
+        let words = (0..word_count)
+            .map(|_| self.read_u16())
+            .collect::<io::Result<Vec<_>>>()?;
+        let operand_values = (0..word_count.saturating_sub(1))
+            .map(|_| self.read_u16())
+            .collect::<io::Result<Vec<_>>>()?;
+        let write_count = self.read_u8()? as usize;
+        let writes = (0..write_count)
+            .map(|_| Ok((self.read_u16()?, self.read_u16()?)))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let cycle = self.cycle;
+        self.cycle += 1;
+        Ok(Some((
+            cycle,
+            TraceRecord {
+                program_counter,
+                words,
+                operand_values,
+                writes,
+            },
+        )))
+    }
+}
+
+/// Address written by an instruction, resolved before it is executed.
+fn write_target(vm: &VirtualMachine, instruction: &Instruction) -> Option<u16> {
+    match instruction {
+        Instruction::Set(target, _)
+        | Instruction::Pop(target)
+        | Instruction::Equality(target, _, _)
+        | Instruction::GreaterThan(target, _, _)
+        | Instruction::Add(target, _, _)
+        | Instruction::Mult(target, _, _)
+        | Instruction::Mod(target, _, _)
+        | Instruction::And(target, _, _)
+        | Instruction::Or(target, _, _)
+        | Instruction::Not(target, _)
+        | Instruction::Load(target, _)
+        | Instruction::In(target) => Some(*target),
+        Instruction::Store(target, _) => Some(resolve_operand(vm, *target)),
+        _ => None,
+    }
+}
+
+fn resolve_operand(vm: &VirtualMachine, operand: u16) -> u16 {
+    vm.memory.resolve(&operand).unwrap_or(operand)
+}
+
+// Trace
+impl VirtualMachine {
+    pub fn start_trace(&mut self, path: &Path) -> io::Result<()> {
+        self.stop_trace()?;
+        self.trace = Some(TraceWriter::create(path, self.cycle as u64)?);
+        Ok(())
+    }
+
+    pub fn stop_trace(&mut self) -> io::Result<()> {
+        match self.trace.take() {
+            Some(trace) => trace.finish(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn begin_trace_record(&self, instruction: &Instruction) -> TraceRecord {
+        let operands = instruction.operands();
+        TraceRecord {
+            program_counter: self.program_counter,
+            words: self.fetch()[..instruction.byte_length()].to_vec(),
+            operand_values: operands
+                .iter()
+                .map(|operand| resolve_operand(self, *operand))
+                .collect(),
+            writes: write_target(self, instruction)
+                .map(|target| (target, 0))
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    pub fn finish_trace_record(&mut self, mut record: TraceRecord) {
+        for (address, value) in record.writes.iter_mut() {
+            *value = match *address {
+                0..=MAX_ADDRESS => self.memory.heap[*address as usize],
+                REGISTER_ADDRESS_START..=REGISTER_ADDRESS_END => {
+                    self.memory.registers[(*address - REGISTER_ADDRESS_START) as usize]
+                }
+                _ => 0,
+            };
+        }
+
+        let result = match &mut self.trace {
+            Some(trace) => trace.write(&record),
+            None => Ok(()),
+        };
+        if let Err(error) = result {
+            self.trace = None;
+            self.status_message = Some(format!("Trace stopped: {}", error));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use crate::vm::VirtualMachine;
+
+    use super::TraceReader;
+
+    const R0: u16 = 32768;
+    const R1: u16 = 32769;
+
+    #[test]
+    fn test_trace_round_trip() {
+        // SET r0 5, WMEM 100 r0, ADD r1 r0 1, HALT
+        let program = [1, R0, 5, 16, 100, R0, 9, R1, R0, 1, 0];
+        let path = env::temp_dir().join(format!("synacor-trace-{}.trace", process::id()));
+
+        let mut vm = VirtualMachine::builder().program(&program).build();
+        vm.run_for(1);
+        vm.start_trace(&path).unwrap();
+        vm.run_until_halt();
+        vm.stop_trace().unwrap();
+        let data = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut reader = TraceReader::new(&data[..]).unwrap();
+        let mut records = vec![];
+        while let Some((cycle, record)) = reader.next_record().unwrap() {
+            records.push((cycle, record.program_counter, record.writes));
+        }
+        assert_eq!(
+            records,
+            vec![
+                (1, 3, vec![(100, 5)]),
+                (2, 6, vec![(R1, 6)]),
+                (3, 10, vec![]),
+            ]
+        );
+
+        // a record cut off in the middle is an error, not the end of the trace
+        let mut reader = TraceReader::new(&data[..data.len() - 3]).unwrap();
+        assert!(reader.next_record().unwrap().is_some());
+        assert!(reader.next_record().unwrap().is_some());
+        assert!(reader.next_record().is_err());
+        assert!(TraceReader::new(&data[..10]).is_err());
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    fs::{self, File},
+    io::{self, LineWriter, Write},
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const TRANSCRIPT_DIRECTORY: &str = "./transcripts";
+
+/// Log of a session with the program output and the consumed input in the order they happened.
+#[derive(Debug)]
+pub struct Transcript {
+    path: PathBuf,
+    writer: LineWriter<File>,
+}
+
+impl Transcript {
+    /// Creates `session-<utc timestamp>-<pid>.txt` in the directory, so parallel sessions
+    /// never share a file.
+    pub fn create(directory: &Path) -> io::Result<Self> {
+        fs::create_dir_all(directory)?;
+        let path = directory.join(format!("session-{}-{}.txt", timestamp(), process::id()));
+        let writer = LineWriter::new(File::create(&path)?);
+        Ok(Self { path, writer })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Failing writes are ignored, a broken transcript must not stop the machine.
+    pub fn record(&mut self, character: char) {
+        let mut buffer = [0; 4];
+        let _ = self
+            .writer
+            .write_all(character.encode_utf8(&mut buffer).as_bytes());
+    }
+}
+
+/// Current UTC time as `YYYYMMDD-HHMMSS`.
+fn timestamp() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, time) = (seconds / 86400, seconds % 86400);
+
+    // days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let shifted = days + 719468;
+    let era = shifted / 146097;
+    let day_of_era = shifted - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
@@ -0,0 +1,232 @@
+use std::fmt::Display;
+
+use super::memory::{AMOUNT_REGISTERS, REGISTER_ADDRESS_END, REGISTER_ADDRESS_START};
+use super::opcodes::format_operand;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub id: usize,
+    /// Heap address or register address (32768..32775).
+    pub address: u16,
+    pub on_read: bool,
+    pub on_write: bool,
+}
+
+impl Display for Watchpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mode = match (self.on_read, self.on_write) {
+            (true, true) => "rw",
+            (true, false) => "r",
+            _ => "w",
+        };
+        write!(f, "#{} {} {}", self.id, format_operand(self.address), mode)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchpointHit {
+    pub id: usize,
+    pub address: u16,
+    pub kind: AccessKind,
+    pub old_value: u16,
+    pub new_value: u16,
+    pub program_counter: u16,
+}
+
+impl Display for WatchpointHit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            AccessKind::Read => write!(
+                f,
+                "Watchpoint #{}: read {} at PC {}: {}",
+                self.id,
+                format_operand(self.address),
+                self.program_counter,
+                self.old_value
+            ),
+            AccessKind::Write => write!(
+                f,
+                "Watchpoint #{}: write {} at PC {}: {} -> {}",
+                self.id,
+                format_operand(self.address),
+                self.program_counter,
+                self.old_value,
+                self.new_value
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Watchpoints {
+    pub entries: Vec<Watchpoint>,
+    /// Hits of the current cycle, the program counter is filled in by the VM.
+    pub hits: Vec<WatchpointHit>,
+}
+
+impl Watchpoints {
+    pub fn add(&mut self, address: u16, on_read: bool, on_write: bool) -> usize {
+        let id = self.entries.iter().map(|w| w.id + 1).max().unwrap_or(0);
+        self.entries.push(Watchpoint {
+            id,
+            address,
+            on_read,
+            on_write,
+        });
+        id
+    }
+
+    pub fn remove(&mut self, id: usize) -> bool {
+        let length = self.entries.len();
+        self.entries.retain(|watchpoint| watchpoint.id != id);
+        length != self.entries.len()
+    }
+
+    pub fn record(&mut self, address: u16, kind: AccessKind, old_value: u16, new_value: u16) {
+        for watchpoint in self.entries.iter() {
+            let watched = match kind {
+                AccessKind::Read => watchpoint.on_read,
+                AccessKind::Write => watchpoint.on_write,
+            };
+            if watched && watchpoint.address == address {
+                self.hits.push(WatchpointHit {
+                    id: watchpoint.id,
+                    address,
+                    kind,
+                    old_value,
+                    new_value,
+                    program_counter: 0,
+                });
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchpointCommand {
+    Add {
+        address: u16,
+        on_read: bool,
+        on_write: bool,
+    },
+    Remove(usize),
+    List,
+}
+
+impl WatchpointCommand {
+    /// Parses the arguments of `!watch`:
+    /// `add <address|r0..r7> [r|w|rw]` (defaults to `w`), `rm <id>` and `list`.
+    pub fn parse(arguments: &[&str]) -> Option<Self> {
+        match arguments.first() {
+            Some(&"add") => {
+                let target = arguments.get(1)?;
+                let address = match target.strip_prefix('r') {
+                    Some(register) => {
+                        let register = register
+                            .parse::<u16>()
+                            .ok()
+                            .filter(|register| (*register as usize) < AMOUNT_REGISTERS)?;
+                        REGISTER_ADDRESS_START + register
+                    }
+                    None => target.parse::<u16>().ok()?,
+                };
+                if address > REGISTER_ADDRESS_END {
+                    return None;
+                }
+                let (on_read, on_write) = match arguments.get(2) {
+                    None | Some(&"w") => (false, true),
+                    Some(&"r") => (true, false),
+                    Some(&"rw") => (true, true),
+                    _ => return None,
+                };
+                Some(Self::Add {
+                    address,
+                    on_read,
+                    on_write,
+                })
+            }
+            Some(&"rm") => Some(Self::Remove(arguments.get(1)?.parse::<usize>().ok()?)),
+            Some(&"list") => Some(Self::List),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::VirtualMachine;
+
+    use super::WatchpointCommand;
+
+    const R0: u16 = 32768;
+    const R1: u16 = 32769;
+    const R7: u16 = 32775;
+
+    /// Cycles until the machine pauses, returns the program counter of the paused cycle.
+    fn run_until_paused(vm: &mut VirtualMachine) -> Option<u16> {
+        while !vm.halted {
+            let program_counter = vm.program_counter;
+            vm.cycle().unwrap();
+            if vm.paused {
+                vm.paused = false;
+                return Some(program_counter);
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_watchpoint_hits() {
+        // SET r0 r7, ADD r1 r7 1, WMEM 100 r0, RMEM r1 100, HALT
+        let program = [1, R0, R7, 9, R1, R7, 1, 16, 100, R0, 15, R1, 100, 0];
+
+        let mut vm = VirtualMachine::builder().program(&program).build();
+        vm.handle_watchpoint_command(WatchpointCommand::parse(&["add", "r7", "r"]).unwrap());
+        assert_eq!(run_until_paused(&mut vm), Some(0));
+        assert_eq!(run_until_paused(&mut vm), Some(3));
+        assert_eq!(run_until_paused(&mut vm), None);
+
+        let mut vm = VirtualMachine::builder().program(&program).build();
+        vm.handle_watchpoint_command(WatchpointCommand::parse(&["add", "100", "rw"]).unwrap());
+        vm.handle_watchpoint_command(WatchpointCommand::parse(&["add", "r1"]).unwrap());
+        assert_eq!(run_until_paused(&mut vm), Some(3));
+        assert_eq!(run_until_paused(&mut vm), Some(7));
+        assert_eq!(
+            vm.status_message.as_deref(),
+            Some("Watchpoint #0: write 100 at PC 7: 0 -> 0")
+        );
+        vm.handle_watchpoint_command(WatchpointCommand::Remove(1));
+        assert_eq!(run_until_paused(&mut vm), Some(10));
+        assert_eq!(
+            vm.status_message.as_deref(),
+            Some("Watchpoint #0: read 100 at PC 10: 0")
+        );
+        assert_eq!(run_until_paused(&mut vm), None);
+        assert_eq!(vm.memory.watchpoints.entries.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_watchpoint_command() {
+        assert_eq!(
+            WatchpointCommand::parse(&["add", "r7", "r"]),
+            Some(WatchpointCommand::Add {
+                address: 32775,
+                on_read: true,
+                on_write: false,
+            })
+        );
+        assert_eq!(WatchpointCommand::parse(&["add", "r8"]), None);
+        assert_eq!(WatchpointCommand::parse(&["add", "r40000"]), None);
+        assert_eq!(WatchpointCommand::parse(&["add", "32776"]), None);
+        assert_eq!(
+            WatchpointCommand::parse(&["rm", "3"]),
+            Some(WatchpointCommand::Remove(3))
+        );
+    }
+}